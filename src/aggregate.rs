@@ -1,8 +1,26 @@
-use crate::Event;
+use crate::{Event, EveryNEventsPolicy, SnapshotPolicy};
 
 pub trait Aggregate: Default + Clone + Send + Sync {
     type Event: Event;
     fn apply(&mut self, event: Self::Event);
     fn version(&self) -> u64;
     fn increment_version(&mut self);
+
+    fn aggregate_type() -> &'static str;
+
+    // Replaying an event stored at `version` leaves the aggregate at exactly
+    // that version, whether or not `apply` already advanced it.
+    fn replay_at(&mut self, event: Self::Event, version: u64) {
+        self.apply(event);
+        while self.version() < version {
+            self.increment_version();
+        }
+    }
+
+    fn replay(&mut self, event: Self::Event) {
+        let version = self.version() + 1;
+        self.replay_at(event, version);
+    }
+
+    fn snapshot_policy() -> Box<dyn SnapshotPolicy> { Box::new(EveryNEventsPolicy::new(10)) }
 }
@@ -0,0 +1,21 @@
+use uuid::Uuid;
+
+pub trait AggregateId: Clone + Send + Sync + 'static {
+    fn encode(&self) -> String;
+
+    fn decode(value: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>>;
+}
+
+impl AggregateId for String {
+    fn encode(&self) -> String { self.clone() }
+
+    fn decode(value: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> { Ok(value.to_string()) }
+}
+
+impl AggregateId for Uuid {
+    fn encode(&self) -> String { self.to_string() }
+
+    fn decode(value: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        Uuid::parse_str(value).map_err(Into::into)
+    }
+}
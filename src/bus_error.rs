@@ -0,0 +1,46 @@
+use std::error::Error;
+
+#[derive(Debug)]
+pub enum BusError {
+    NoHandler { message_type: &'static str },
+    TypeMismatch { expected: &'static str },
+    Handler(Box<dyn Error + Send + Sync>),
+    ConcurrencyConflict(Box<dyn Error + Send + Sync>),
+}
+
+impl BusError {
+    pub fn handler_error(&self) -> Option<&(dyn Error + Send + Sync + 'static)> {
+        match self {
+            BusError::Handler(err) | BusError::ConcurrencyConflict(err) => Some(err.as_ref()),
+            _ => None,
+        }
+    }
+
+    pub fn is_concurrency_conflict(&self) -> bool { matches!(self, BusError::ConcurrencyConflict(_)) }
+
+    pub fn downcast_ref<T: Error + 'static>(&self) -> Option<&T> { self.handler_error()?.downcast_ref::<T>() }
+}
+
+impl std::fmt::Display for BusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BusError::NoHandler {
+                message_type,
+            } => write!(f, "No handler registered for {}", message_type),
+            BusError::TypeMismatch {
+                expected,
+            } => write!(f, "Type mismatch, expected {}", expected),
+            BusError::Handler(err) => write!(f, "Handler failed: {}", err),
+            BusError::ConcurrencyConflict(err) => write!(f, "Concurrency conflict: {}", err),
+        }
+    }
+}
+
+impl Error for BusError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            BusError::Handler(err) | BusError::ConcurrencyConflict(err) => Some(err.as_ref()),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,39 @@
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::RwLock;
+
+use async_trait::async_trait;
+
+#[async_trait]
+pub trait CheckpointStore {
+    type Error;
+
+    async fn load_checkpoint(&self, subscription: &str) -> Result<Option<u64>, Self::Error>;
+    async fn save_checkpoint(&self, subscription: &str, position: u64) -> Result<(), Self::Error>;
+}
+
+#[derive(Default)]
+pub struct InMemoryCheckpointStore {
+    checkpoints: RwLock<HashMap<String, u64>>,
+}
+
+impl InMemoryCheckpointStore {
+    pub fn new() -> Self { Self::default() }
+}
+
+#[async_trait]
+impl CheckpointStore for InMemoryCheckpointStore {
+    type Error = Infallible;
+
+    async fn load_checkpoint(&self, subscription: &str) -> Result<Option<u64>, Self::Error> {
+        Ok(self.checkpoints.read().unwrap().get(subscription).copied())
+    }
+
+    async fn save_checkpoint(&self, subscription: &str, position: u64) -> Result<(), Self::Error> {
+        self.checkpoints
+            .write()
+            .unwrap()
+            .insert(subscription.to_string(), position);
+        Ok(())
+    }
+}
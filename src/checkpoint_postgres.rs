@@ -0,0 +1,45 @@
+use async_trait::async_trait;
+use sqlx::PgPool;
+
+use crate::CheckpointStore;
+use crate::event_store_postgres::PostgresError;
+
+#[derive(Clone)]
+pub struct PostgresCheckpointStore {
+    pub(crate) pool: PgPool,
+}
+
+impl PostgresCheckpointStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            pool,
+        }
+    }
+}
+
+#[async_trait]
+impl CheckpointStore for PostgresCheckpointStore {
+    type Error = PostgresError;
+
+    async fn load_checkpoint(&self, subscription: &str) -> Result<Option<u64>, Self::Error> {
+        let position: Option<i64> = sqlx::query_scalar("SELECT position FROM checkpoints WHERE subscription = $1")
+            .bind(subscription)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(position.map(|position| position as u64))
+    }
+
+    async fn save_checkpoint(&self, subscription: &str, position: u64) -> Result<(), Self::Error> {
+        sqlx::query(
+            "INSERT INTO checkpoints (subscription, position) VALUES ($1, $2) ON CONFLICT (subscription) DO UPDATE \
+             SET position = EXCLUDED.position, updated_at = NOW()",
+        )
+        .bind(subscription)
+        .bind(position as i64)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
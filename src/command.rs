@@ -1,4 +1,6 @@
-use crate::Aggregate;
+use std::any::Any;
+
+use crate::{Aggregate, EventMetadata, MessageContext};
 
 pub trait Command: Send + Sync {
     type Aggregate: Aggregate;
@@ -8,4 +10,29 @@ pub trait Command: Send + Sync {
     fn aggregate_id(&self) -> &Self::AggregateId;
 
     fn execute(&self, aggregate: &Self::Aggregate) -> Result<Vec<<Self::Aggregate as Aggregate>::Event>, Self::Error>;
+
+    fn context(&self) -> Option<&MessageContext> { None }
+}
+
+pub trait CommandOutput: Command {
+    type Output: Send + 'static;
+
+    fn output(&self, aggregate: &Self::Aggregate) -> Self::Output;
+}
+
+#[derive(Debug, Clone)]
+pub struct CommandResult<O = ()> {
+    pub output: O,
+    pub version: u64,
+    pub events: Vec<EventMetadata>,
+}
+
+// What command handlers hand to `Middleware::handle`; the output is still
+// erased, but the version and event metadata can be read directly.
+pub type ErasedCommandResult = CommandResult<Box<dyn Any + Send>>;
+
+impl ErasedCommandResult {
+    pub fn from_any(result: &(dyn Any + Send)) -> Option<&Self> { result.downcast_ref::<Self>() }
+
+    pub fn output_ref<O: 'static>(&self) -> Option<&O> { self.output.downcast_ref::<O>() }
 }
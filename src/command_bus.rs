@@ -1,53 +1,94 @@
-use std::any::TypeId;
+use std::any::{Any, TypeId};
 use std::collections::HashMap;
 
 use async_trait::async_trait;
 
-use crate::{Command, CommandHandler};
+use crate::middleware::Next;
+use crate::{
+    AggregateId, BusError, Command, CommandHandler, CommandHandlerError, CommandOutput, CommandResult,
+    ErasedCommandResult, Middleware,
+};
 
 #[async_trait]
 pub trait CommandBus {
     type Error;
 
-    async fn send<C: Command + 'static>(&self, command: C) -> Result<(), Self::Error>;
+    async fn send<C: Command + 'static>(&self, command: C) -> Result<CommandResult, Self::Error>;
+
+    async fn send_with_output<C: CommandOutput + 'static>(
+        &self, command: C,
+    ) -> Result<CommandResult<C::Output>, Self::Error>;
 }
 
 #[async_trait]
 pub trait ErasedCommandHandler: Send + Sync {
-    async fn handle(
-        &self, command: Box<dyn std::any::Any + Send>,
-    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>>;
+    async fn handle(&self, command: &(dyn Any + Send + Sync)) -> Result<Box<dyn Any + Send>, BusError>;
+    fn output_type(&self) -> TypeId;
 }
 
+type ErasedOutput<C> = fn(&C, &<C as Command>::Aggregate) -> Box<dyn Any + Send>;
+
 pub struct TypedCommandHandler<C: Command, H: CommandHandler<C>> {
     handler: H,
-    _phantom: std::marker::PhantomData<C>,
+    output: ErasedOutput<C>,
+    output_type: TypeId,
 }
 
 impl<C: Command, H: CommandHandler<C>> TypedCommandHandler<C, H> {
     pub fn new(handler: H) -> Self {
         Self {
             handler,
-            _phantom: std::marker::PhantomData,
+            output: |_, _| Box::new(()),
+            output_type: TypeId::of::<()>(),
+        }
+    }
+
+    pub fn with_output(handler: H) -> Self
+    where
+        C: CommandOutput,
+    {
+        Self {
+            handler,
+            output: |command, aggregate| Box::new(command.output(aggregate)),
+            output_type: TypeId::of::<C::Output>(),
         }
     }
 }
 
 #[async_trait]
-impl<C: Command + 'static, H: CommandHandler<C> + Send + Sync> ErasedCommandHandler for TypedCommandHandler<C, H> {
-    async fn handle(
-        &self, command: Box<dyn std::any::Any + Send>,
-    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        let typed_command = *command.downcast::<C>().map_err(|_| "Type mismatch")?;
-        self.handler
-            .handle(typed_command)
+impl<C: Command + 'static, H: CommandHandler<C> + Send + Sync> ErasedCommandHandler for TypedCommandHandler<C, H>
+where
+    C::AggregateId: AggregateId,
+    C::Error: std::error::Error + Send + Sync + 'static,
+    H::Error: std::error::Error + Send + Sync + 'static,
+{
+    async fn handle(&self, command: &(dyn Any + Send + Sync)) -> Result<Box<dyn Any + Send>, BusError> {
+        let typed_command = command.downcast_ref::<C>().ok_or_else(|| {
+            BusError::TypeMismatch {
+                expected: std::any::type_name::<C>(),
+            }
+        })?;
+
+        let result = self
+            .handler
+            .process(typed_command, |aggregate| (self.output)(typed_command, aggregate))
             .await
-            .map_err(|_| "Command execution failed".into())
+            .map_err(|err| {
+                if err.is_concurrency_conflict() {
+                    BusError::ConcurrencyConflict(Box::new(err))
+                } else {
+                    BusError::Handler(Box::new(err))
+                }
+            })?;
+        Ok(Box::new(result))
     }
+
+    fn output_type(&self) -> TypeId { self.output_type }
 }
 
 pub struct InMemoryCommandBus {
     handlers: HashMap<TypeId, Box<dyn ErasedCommandHandler>>,
+    middleware: Vec<Box<dyn Middleware>>,
 }
 
 impl Default for InMemoryCommandBus {
@@ -58,29 +99,100 @@ impl InMemoryCommandBus {
     pub fn new() -> Self {
         Self {
             handlers: HashMap::new(),
+            middleware: Vec::new(),
         }
     }
 
+    pub fn with_middleware<M: Middleware + 'static>(mut self, middleware: M) -> Self {
+        self.middleware.push(Box::new(middleware));
+        self
+    }
+
     pub fn register_handler<C, H>(&mut self, handler: H)
     where
         C: Command + 'static,
+        C::AggregateId: AggregateId,
+        C::Error: std::error::Error + Send + Sync + 'static,
         H: CommandHandler<C> + Send + Sync + 'static,
+        H::Error: std::error::Error + Send + Sync + 'static,
     {
         log::info!("Registering command handler for: {}", std::any::type_name::<C>());
 
         let wrapped = TypedCommandHandler::new(handler);
         self.handlers.insert(TypeId::of::<C>(), Box::new(wrapped));
     }
+
+    pub fn register_handler_with_output<C, H>(&mut self, handler: H)
+    where
+        C: CommandOutput + 'static,
+        C::AggregateId: AggregateId,
+        C::Error: std::error::Error + Send + Sync + 'static,
+        H: CommandHandler<C> + Send + Sync + 'static,
+        H::Error: std::error::Error + Send + Sync + 'static,
+    {
+        log::info!(
+            "Registering command handler with output for: {}",
+            std::any::type_name::<C>()
+        );
+
+        let wrapped = TypedCommandHandler::with_output(handler);
+        self.handlers.insert(TypeId::of::<C>(), Box::new(wrapped));
+    }
+
+    async fn dispatch<C: Command + 'static>(
+        &self, command: C, output: Option<(TypeId, &'static str)>,
+    ) -> Result<ErasedCommandResult, BusError> {
+        let handler = self.handlers.get(&TypeId::of::<C>()).ok_or(BusError::NoHandler {
+            message_type: std::any::type_name::<C>(),
+        })?;
+
+        if let Some((output_type, expected)) = output
+            && handler.output_type() != output_type
+        {
+            return Err(BusError::TypeMismatch {
+                expected,
+            });
+        }
+
+        let result = Next::new(&self.middleware, handler)
+            .run(std::any::type_name::<C>(), &command)
+            .await?;
+        let typed_result = *result.downcast::<ErasedCommandResult>().map_err(|_| {
+            BusError::TypeMismatch {
+                expected: std::any::type_name::<CommandResult>(),
+            }
+        })?;
+        Ok(typed_result)
+    }
 }
 
 #[async_trait]
 impl CommandBus for InMemoryCommandBus {
-    type Error = Box<dyn std::error::Error + Send + Sync>;
-
-    async fn send<C: Command + 'static>(&self, command: C) -> Result<(), Self::Error> {
-        let type_id = TypeId::of::<C>();
-        let handler = self.handlers.get(&type_id).ok_or("No handler registered for command")?;
+    type Error = BusError;
+
+    async fn send<C: Command + 'static>(&self, command: C) -> Result<CommandResult, Self::Error> {
+        let result = self.dispatch(command, None).await?;
+        Ok(CommandResult {
+            output: (),
+            version: result.version,
+            events: result.events,
+        })
+    }
 
-        handler.handle(Box::new(command)).await
+    async fn send_with_output<C: CommandOutput + 'static>(
+        &self, command: C,
+    ) -> Result<CommandResult<C::Output>, Self::Error> {
+        let output_type = (TypeId::of::<C::Output>(), std::any::type_name::<C::Output>());
+        let result = self.dispatch(command, Some(output_type)).await?;
+        let output = *result.output.downcast::<C::Output>().map_err(|_| {
+            BusError::TypeMismatch {
+                expected: std::any::type_name::<C::Output>(),
+            }
+        })?;
+        Ok(CommandResult {
+            output,
+            version: result.version,
+            events: result.events,
+        })
     }
 }
@@ -1,16 +1,20 @@
 use async_trait::async_trait;
-use uuid::Uuid;
 
-use crate::{Aggregate, Command, EventBus, EventEnvelope, EventMetadata, EventStore, SnapshotStore};
+use crate::{
+    Aggregate, AggregateId, AggregateTypeMismatch, Command, CommandOutput, CommandResult, EventBus, EventEnvelope,
+    EventMetadata, EventStore, EventStoreError, MessageContext, RetryPolicy, SnapshotContext, SnapshotStore,
+};
 
 pub trait CommandHandlerError {
-    fn from_event_store_error<E>(err: E) -> Self;
-    fn from_command_error<E>(err: E) -> Self;
+    fn from_event_store_error<E: std::error::Error + Send + Sync + 'static>(err: E) -> Self;
+    fn from_command_error<E: std::error::Error + Send + Sync + 'static>(err: E) -> Self;
+    fn from_concurrency_conflict<E: std::error::Error + Send + Sync + 'static>(err: E) -> Self;
+    fn is_concurrency_conflict(&self) -> bool;
 }
 
 #[async_trait]
 pub trait CommandHandler<C: Command> {
-    type EventStore: EventStore<<C::Aggregate as Aggregate>::Event, C::AggregateId>;
+    type EventStore: EventStore<<C::Aggregate as Aggregate>::Event, C::AggregateId, Error: EventStoreError> + Sync;
     type SnapshotStore: SnapshotStore<C::Aggregate, C::AggregateId>;
     type EventBus: EventBus<<C::Aggregate as Aggregate>::Event>;
     type Error: CommandHandlerError;
@@ -19,42 +23,96 @@ pub trait CommandHandler<C: Command> {
     fn snapshot_store(&self) -> &Self::SnapshotStore;
     fn event_bus(&self) -> &Self::EventBus;
 
-    async fn handle(&self, command: C) -> Result<(), Self::Error>
+    fn retry_policy(&self) -> RetryPolicy { RetryPolicy::none() }
+
+    async fn handle(&self, command: C) -> Result<CommandResult, Self::Error>
     where
         C: 'static,
+        C::AggregateId: AggregateId,
+        C::Error: std::error::Error + Send + Sync + 'static,
+    {
+        self.process(&command, |_| ()).await
+    }
+
+    async fn handle_with_output(&self, command: C) -> Result<CommandResult<C::Output>, Self::Error>
+    where
+        C: CommandOutput + 'static,
+        C::AggregateId: AggregateId,
+        C::Error: std::error::Error + Send + Sync + 'static,
+    {
+        self.process(&command, |aggregate| command.output(aggregate)).await
+    }
+
+    async fn process<O, F>(&self, command: &C, output: F) -> Result<CommandResult<O>, Self::Error>
+    where
+        C: 'static,
+        C::AggregateId: AggregateId,
+        C::Error: std::error::Error + Send + Sync + 'static,
+        O: Send + 'static,
+        F: FnOnce(&C::Aggregate) -> O + Send,
     {
         log::info!("Processing command: {}", std::any::type_name::<C>());
 
-        let snapshot = self
-            .snapshot_store()
-            .get_snapshot(command.aggregate_id())
-            .await
-            .ok()
-            .flatten();
+        let aggregate_type = C::Aggregate::aggregate_type();
+        if let Some(found) = self.event_store().aggregate_type()
+            && found != aggregate_type
+        {
+            return Err(Self::Error::from_event_store_error(AggregateTypeMismatch {
+                expected: aggregate_type,
+                found: found.to_string(),
+            }));
+        }
 
-        let mut aggregate = snapshot.unwrap_or_default();
-        let from_version = aggregate.version();
+        let retry_policy = self.retry_policy();
+        let mut attempt = 1;
 
-        log::debug!("Loaded aggregate from version: {}", from_version);
+        loop {
+            let snapshot = self
+                .snapshot_store()
+                .get_snapshot(command.aggregate_id())
+                .await
+                .ok()
+                .flatten();
 
-        let events = self
-            .event_store()
-            .get_events_from_version(command.aggregate_id(), from_version)
-            .await
-            .map_err(Self::Error::from_event_store_error)?;
+            let mut aggregate = snapshot.unwrap_or_default();
+            let from_version = aggregate.version();
 
-        log::debug!("Loaded {} events from event store", events.len());
+            log::debug!("Loaded aggregate from version: {}", from_version);
 
-        for envelope in events {
-            aggregate.apply(envelope.event);
-        }
+            let events = self
+                .event_store()
+                .get_stream_from_version(command.aggregate_id(), from_version)
+                .await
+                .map_err(Self::Error::from_event_store_error)?;
 
-        let new_events = command.execute(&aggregate).map_err(Self::Error::from_command_error)?;
+            log::debug!("Loaded {} events from event store", events.len());
+
+            let mut oldest_unsnapshotted_event_at = events.first().map(|(_, envelope)| envelope.metadata.timestamp);
+
+            // Upcasting can split one stored event into several that share its version.
+            for (version, envelope) in events {
+                aggregate.replay_at(envelope.event, version);
+            }
+
+            let new_events = command.execute(&aggregate).map_err(Self::Error::from_command_error)?;
+
+            if new_events.is_empty() {
+                log::debug!("No events generated");
+                return Ok(CommandResult {
+                    output: output(&aggregate),
+                    version: aggregate.version(),
+                    events: Vec::new(),
+                });
+            }
 
-        if !new_events.is_empty() {
             log::info!("Generated {} new events", new_events.len());
 
-            let correlation_id = Uuid::new_v4();
+            let context = command
+                .context()
+                .cloned()
+                .or_else(MessageContext::current)
+                .unwrap_or_default();
+            let aggregate_id = command.aggregate_id().encode();
             let envelopes: Vec<_> = new_events
                 .into_iter()
                 .map(|event| {
@@ -62,39 +120,81 @@ pub trait CommandHandler<C: Command> {
 
                     EventEnvelope {
                         event,
-                        metadata: EventMetadata::new(correlation_id, None),
+                        metadata: EventMetadata::from_context(&context)
+                            .with_aggregate_type(aggregate_type)
+                            .with_aggregate_id(aggregate_id.clone()),
                     }
                 })
                 .collect();
 
-            let envelope_count = envelopes.len() as u64;
+            let previous_version = aggregate.version();
+            oldest_unsnapshotted_event_at =
+                oldest_unsnapshotted_event_at.or_else(|| envelopes.first().map(|envelope| envelope.metadata.timestamp));
 
-            self.event_store()
-                .save_events(command.aggregate_id(), envelopes.clone(), aggregate.version())
+            let retry = match self
+                .event_store()
+                .save_events(command.aggregate_id(), envelopes.clone(), previous_version)
                 .await
-                .map_err(Self::Error::from_event_store_error)?;
+            {
+                Ok(()) => false,
+                Err(err) if err.is_concurrency_conflict() => {
+                    if !retry_policy.should_retry(attempt) {
+                        log::warn!("Concurrency conflict after {} attempts, giving up", attempt);
+                        return Err(Self::Error::from_concurrency_conflict(err));
+                    }
+                    true
+                },
+                Err(err) => return Err(Self::Error::from_event_store_error(err)),
+            };
+
+            if retry {
+                let backoff = retry_policy.backoff(attempt);
+                log::info!("Concurrency conflict on attempt {}, retrying in {:?}", attempt, backoff);
+
+                tokio::time::sleep(backoff).await;
+                attempt += 1;
+                continue;
+            }
 
             log::info!("Saved events to event store");
 
-            self.event_bus().publish(&envelopes).await.ok();
+            if self.event_store().uses_outbox() {
+                log::debug!("Leaving publication to the outbox relay");
+            } else if self.event_bus().publish(&envelopes).await.is_ok() {
+                log::info!("Published events to event bus");
+            } else {
+                log::warn!("Failed to publish events to event bus");
+            }
+
+            let mut events = Vec::with_capacity(envelopes.len());
+            for (version, envelope) in (previous_version + 1..).zip(envelopes) {
+                aggregate.replay_at(envelope.event, version);
+                events.push(envelope.metadata);
+            }
 
-            log::info!("Published events to event bus");
+            let result = CommandResult {
+                output: output(&aggregate),
+                version: aggregate.version(),
+                events,
+            };
 
-            if (aggregate.version() + envelope_count) % 10 == 0 {
-                log::info!(
-                    "Creating snapshot at version {}",
-                    aggregate.version() + envelopes.len() as u64
-                );
+            let snapshot_context = SnapshotContext {
+                last_snapshot_version: from_version,
+                previous_version,
+                current_version: aggregate.version(),
+                oldest_unsnapshotted_event_at,
+            };
+
+            if C::Aggregate::snapshot_policy().should_snapshot(&snapshot_context) {
+                log::info!("Creating snapshot at version {}", snapshot_context.current_version);
 
                 self.snapshot_store()
                     .save_snapshot(command.aggregate_id(), aggregate)
                     .await
                     .ok();
             }
-        } else {
-            log::debug!("No events generated");
-        }
 
-        Ok(())
+            return Ok(result);
+        }
     }
 }
@@ -2,4 +2,6 @@ use std::fmt::Debug;
 
 pub trait Event: Debug + Clone + Send + Sync {
     fn event_type(&self) -> &'static str;
+
+    fn schema_version(&self) -> u32 { 1 }
 }
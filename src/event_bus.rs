@@ -1,31 +1,120 @@
+use std::collections::BTreeSet;
+use std::sync::{Arc, Mutex};
+
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures_util::StreamExt;
+use uuid::Uuid;
 
-use crate::{Event, EventEnvelope, EventHandler};
+use crate::{Event, EventEnvelope, EventFilter, EventHandler, MessageContext, RetryPolicy};
 
 #[async_trait]
 pub trait EventBus<E: Event> {
     type Error;
 
     async fn publish(&self, events: &[EventEnvelope<E>]) -> Result<(), Self::Error>;
-    fn subscribe<H: EventHandler<E> + Send + Sync + 'static>(&mut self, handler: H);
+    fn subscribe<H: EventHandler<E> + Send + Sync + 'static>(&mut self, handler: H)
+    where
+        H::Error: std::error::Error + Send + Sync + 'static;
 }
 
 #[async_trait]
-trait ErasedEventHandler<E: Event>: Send + Sync {
+pub(crate) trait ErasedEventHandler<E: Event>: Send + Sync {
     async fn handle(&self, event: &EventEnvelope<E>) -> Result<(), Box<dyn std::error::Error + Send + Sync>>;
 }
 
 #[async_trait]
-impl<E: Event, H: EventHandler<E> + Send + Sync> ErasedEventHandler<E> for H {
+impl<E: Event, H: EventHandler<E> + Send + Sync> ErasedEventHandler<E> for H
+where
+    H::Error: std::error::Error + Send + Sync + 'static,
+{
     async fn handle(&self, event: &EventEnvelope<E>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        EventHandler::handle(self, event)
-            .await
-            .map_err(|_| "Event handler failed".into())
+        EventHandler::handle(self, event).await.map_err(|err| err.into())
+    }
+}
+
+pub(crate) async fn handle_with_retry<E: Event>(
+    handler: &dyn ErasedEventHandler<E>, retry_policy: &RetryPolicy, propagated_headers: &BTreeSet<String>,
+    event: &EventEnvelope<E>,
+) -> Result<(), (u32, Box<dyn std::error::Error + Send + Sync>)> {
+    let context = MessageContext::from_metadata(&event.metadata, propagated_headers);
+    let mut attempt = 1;
+
+    loop {
+        match context.clone().scope(handler.handle(event)).await {
+            Ok(()) => return Ok(()),
+            Err(err) if !retry_policy.should_retry(attempt) => return Err((attempt, err)),
+            Err(err) => {
+                log::debug!("Handler attempt {} failed: {}", attempt, err);
+            },
+        }
+
+        tokio::time::sleep(retry_policy.backoff(attempt)).await;
+        attempt += 1;
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct DeadLetter<E: Event> {
+    pub envelope: EventEnvelope<E>,
+    pub handler: &'static str,
+    pub subscription: usize,
+    pub error: Arc<dyn std::error::Error + Send + Sync>,
+    pub attempts: u32,
+    pub failed_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DispatchMode {
+    #[default]
+    Sequential,
+    Concurrent {
+        max_concurrency: usize,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct HandlerFailure {
+    pub handler: &'static str,
+    pub event_id: Uuid,
+    pub event_type: &'static str,
+    pub error: Arc<dyn std::error::Error + Send + Sync>,
+    pub attempts: u32,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DispatchReport {
+    pub failures: Vec<HandlerFailure>,
+}
+
+impl std::fmt::Display for DispatchReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} event deliveries failed", self.failures.len())?;
+        for failure in &self.failures {
+            write!(
+                f,
+                "; {} on {} ({}): {}",
+                failure.handler, failure.event_type, failure.event_id, failure.error
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for DispatchReport {}
+
+struct Subscription<E: Event> {
+    name: &'static str,
+    handler: Box<dyn ErasedEventHandler<E>>,
+    filter: EventFilter,
+    retry_policy: RetryPolicy,
+}
+
 pub struct InMemoryEventBus<E: Event> {
-    handlers: Vec<Box<dyn ErasedEventHandler<E>>>,
+    handlers: Vec<Subscription<E>>,
+    dead_letters: Mutex<Vec<DeadLetter<E>>>,
+    dispatch_mode: DispatchMode,
+    propagated_headers: BTreeSet<String>,
 }
 
 impl<E: Event> Default for InMemoryEventBus<E> {
@@ -36,25 +125,171 @@ impl<E: Event> InMemoryEventBus<E> {
     pub fn new() -> Self {
         Self {
             handlers: Vec::new(),
+            dead_letters: Mutex::new(Vec::new()),
+            dispatch_mode: DispatchMode::default(),
+            propagated_headers: BTreeSet::new(),
+        }
+    }
+
+    pub fn with_dispatch_mode(mut self, dispatch_mode: DispatchMode) -> Self {
+        self.dispatch_mode = dispatch_mode;
+        self
+    }
+
+    pub fn with_propagated_headers<K: Into<String>>(mut self, keys: impl IntoIterator<Item = K>) -> Self {
+        self.propagated_headers.extend(keys.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn subscribe_with_retry<H: EventHandler<E> + Send + Sync + 'static>(
+        &mut self, handler: H, retry_policy: RetryPolicy,
+    ) where
+        H::Error: std::error::Error + Send + Sync + 'static,
+    {
+        self.subscribe_with(handler, EventFilter::all(), retry_policy);
+    }
+
+    pub fn subscribe_filtered<H: EventHandler<E> + Send + Sync + 'static>(&mut self, handler: H, filter: EventFilter)
+    where
+        H::Error: std::error::Error + Send + Sync + 'static,
+    {
+        self.subscribe_with(handler, filter, RetryPolicy::none());
+    }
+
+    pub fn subscribe_with<H: EventHandler<E> + Send + Sync + 'static>(
+        &mut self, handler: H, filter: EventFilter, retry_policy: RetryPolicy,
+    ) where
+        H::Error: std::error::Error + Send + Sync + 'static,
+    {
+        log::info!("Registering new event handler (total: {})", self.handlers.len() + 1);
+
+        self.handlers.push(Subscription {
+            name: std::any::type_name::<H>(),
+            handler: Box::new(handler),
+            filter,
+            retry_policy,
+        });
+    }
+
+    pub fn dead_letters(&self) -> Vec<DeadLetter<E>> { self.dead_letters.lock().unwrap().clone() }
+
+    pub fn clear_dead_letters(&self) { self.dead_letters.lock().unwrap().clear(); }
+
+    pub async fn replay_dead_letters(&self) -> usize {
+        let dead_letters = std::mem::take(&mut *self.dead_letters.lock().unwrap());
+
+        log::info!("Replaying {} dead letters", dead_letters.len());
+
+        let mut replayed = 0;
+        for dead_letter in dead_letters {
+            if self
+                .deliver(dead_letter.subscription, &dead_letter.envelope)
+                .await
+                .is_ok()
+            {
+                replayed += 1;
+            }
+        }
+
+        replayed
+    }
+
+    async fn deliver(&self, index: usize, event: &EventEnvelope<E>) -> Result<(), HandlerFailure> {
+        let subscription = &self.handlers[index];
+
+        if !subscription.filter.matches(event) {
+            return Ok(());
+        }
+
+        log::debug!("Calling handler {}", index);
+
+        match handle_with_retry(
+            subscription.handler.as_ref(),
+            &subscription.retry_policy,
+            &self.propagated_headers,
+            event,
+        )
+        .await
+        {
+            Ok(()) => Ok(()),
+            Err((attempts, err)) => {
+                let err: Arc<dyn std::error::Error + Send + Sync> = Arc::from(err);
+
+                log::warn!(
+                    "Handler {} failed on event {} after {} attempts, dead-lettering: {}",
+                    subscription.name,
+                    event.metadata.event_id,
+                    attempts,
+                    err
+                );
+
+                self.dead_letters.lock().unwrap().push(DeadLetter {
+                    envelope: event.clone(),
+                    handler: subscription.name,
+                    subscription: index,
+                    error: err.clone(),
+                    attempts,
+                    failed_at: Utc::now(),
+                });
+
+                Err(HandlerFailure {
+                    handler: subscription.name,
+                    event_id: event.metadata.event_id,
+                    event_type: event.event.event_type(),
+                    error: err,
+                    attempts,
+                })
+            },
         }
     }
 }
 
 #[async_trait]
 impl<E: Event> EventBus<E> for InMemoryEventBus<E> {
-    type Error = Box<dyn std::error::Error + Send + Sync>;
+    type Error = DispatchReport;
 
     async fn publish(&self, events: &[EventEnvelope<E>]) -> Result<(), Self::Error> {
         log::info!("Publishing {} events to {} handlers", events.len(), self.handlers.len());
 
-        for event in events {
-            log::debug!("Processing event: {}", event.event.event_type());
+        let failures = match self.dispatch_mode {
+            DispatchMode::Sequential => {
+                let mut failures = Vec::new();
+                for event in events {
+                    log::debug!("Processing event: {}", event.event.event_type());
 
-            for (i, handler) in self.handlers.iter().enumerate() {
-                log::debug!("Calling handler {}", i);
+                    for index in 0..self.handlers.len() {
+                        if let Err(failure) = self.deliver(index, event).await {
+                            failures.push(failure);
+                        }
+                    }
+                }
+                failures
+            },
+            DispatchMode::Concurrent {
+                max_concurrency,
+            } => {
+                futures_util::stream::iter(0..self.handlers.len())
+                    .map(|index| {
+                        async move {
+                            let mut failures = Vec::new();
+                            for event in events {
+                                if let Err(failure) = self.deliver(index, event).await {
+                                    failures.push(failure);
+                                }
+                            }
+                            failures
+                        }
+                    })
+                    .buffer_unordered(max_concurrency.max(1))
+                    .concat()
+                    .await
+            },
+        };
 
-                handler.handle(event).await?;
-            }
+        if !failures.is_empty() {
+            return Err(DispatchReport {
+                failures,
+            });
         }
 
         log::debug!("All events published successfully");
@@ -62,9 +297,10 @@ impl<E: Event> EventBus<E> for InMemoryEventBus<E> {
         Ok(())
     }
 
-    fn subscribe<H: EventHandler<E> + Send + Sync + 'static>(&mut self, handler: H) {
-        log::info!("Registering new event handler (total: {})", self.handlers.len() + 1);
-
-        self.handlers.push(Box::new(handler));
+    fn subscribe<H: EventHandler<E> + Send + Sync + 'static>(&mut self, handler: H)
+    where
+        H::Error: std::error::Error + Send + Sync + 'static,
+    {
+        self.subscribe_with_retry(handler, RetryPolicy::none());
     }
 }
@@ -1,70 +1,443 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+
 use async_trait::async_trait;
+use futures_util::{Stream, StreamExt};
 use lapin::options::*;
-use lapin::types::FieldTable;
+use lapin::publisher_confirm::Confirmation;
+use lapin::types::{AMQPValue, FieldTable};
 use lapin::{BasicProperties, Channel, Connection, ConnectionProperties};
 use serde::Serialize;
+use tokio::sync::{Mutex, watch};
+use uuid::Uuid;
+
+use crate::{Event, EventBus, EventEnvelope, RetryPolicy};
 
-use crate::{Event, EventBus, EventEnvelope};
+const PERSISTENT_DELIVERY_MODE: u8 = 2;
+const MIN_RECONNECT_BACKOFF: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    Connected,
+    Reconnecting,
+    Disconnected,
+}
 
 #[derive(Clone)]
 pub struct RabbitEventBus {
-    channel: Channel,
+    inner: Arc<RabbitConnection>,
+}
+
+struct RabbitConnection {
+    amqp_url: String,
     exchange: String,
+    connection: Mutex<Option<(u64, Connection, Channel)>>,
+    generation: AtomicU64,
+    reconnecting: Mutex<()>,
+    state: watch::Sender<ConnectionState>,
+    reconnect_policy: RetryPolicy,
 }
 
-impl RabbitEventBus {
-    pub async fn new(amqp_url: &str, exchange: String) -> Result<Self, lapin::Error> {
-        let conn = Connection::connect(amqp_url, ConnectionProperties::default()).await?;
+#[derive(Debug)]
+pub enum RabbitError {
+    Lapin(lapin::Error),
+    Serialization(serde_json::Error),
+    Nacked {
+        event_id: Uuid,
+        routing_key: String,
+    },
+    Returned {
+        event_id: Uuid,
+        routing_key: String,
+        reply_code: u16,
+        reply_text: String,
+    },
+}
+
+impl std::fmt::Display for RabbitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RabbitError::Lapin(err) => write!(f, "RabbitMQ error: {}", err),
+            RabbitError::Serialization(err) => write!(f, "Serialization error: {}", err),
+            RabbitError::Nacked {
+                event_id,
+                routing_key,
+            } => write!(f, "Broker rejected event {} ({})", event_id, routing_key),
+            RabbitError::Returned {
+                event_id,
+                routing_key,
+                reply_code,
+                reply_text,
+            } => {
+                write!(
+                    f,
+                    "Event {} ({}) was returned as unroutable: {} {}",
+                    event_id, routing_key, reply_code, reply_text
+                )
+            },
+        }
+    }
+}
+
+impl std::error::Error for RabbitError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RabbitError::Lapin(err) => Some(err),
+            RabbitError::Serialization(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<lapin::Error> for RabbitError {
+    fn from(err: lapin::Error) -> Self { RabbitError::Lapin(err) }
+}
+
+impl From<serde_json::Error> for RabbitError {
+    fn from(err: serde_json::Error) -> Self { RabbitError::Serialization(err) }
+}
+
+fn message_properties<E: Event>(envelope: &EventEnvelope<E>) -> BasicProperties {
+    let metadata = &envelope.metadata;
+
+    let mut headers = FieldTable::default();
+    for (key, value) in &metadata.headers {
+        headers.insert(key.as_str().into(), AMQPValue::LongString(value.as_str().into()));
+    }
+    headers.insert(
+        "event_type".into(),
+        AMQPValue::LongString(envelope.event.event_type().into()),
+    );
+    if let Some(aggregate_type) = &metadata.aggregate_type {
+        headers.insert(
+            "aggregate_type".into(),
+            AMQPValue::LongString(aggregate_type.as_str().into()),
+        );
+    }
+    if let Some(aggregate_id) = &metadata.aggregate_id {
+        headers.insert(
+            "aggregate_id".into(),
+            AMQPValue::LongString(aggregate_id.as_str().into()),
+        );
+    }
+    if let Some(causation_id) = metadata.causation_id {
+        headers.insert(
+            "causation_id".into(),
+            AMQPValue::LongString(causation_id.to_string().into()),
+        );
+    }
+    if let Some(user_id) = &metadata.user_id {
+        headers.insert("user_id".into(), AMQPValue::LongString(user_id.as_str().into()));
+    }
+    if let Some(tenant_id) = &metadata.tenant_id {
+        headers.insert("tenant_id".into(), AMQPValue::LongString(tenant_id.as_str().into()));
+    }
+
+    BasicProperties::default()
+        .with_message_id(metadata.event_id.to_string().into())
+        .with_correlation_id(metadata.correlation_id.to_string().into())
+        .with_timestamp(metadata.timestamp.timestamp().max(0) as u64)
+        .with_content_type("application/json".into())
+        .with_delivery_mode(PERSISTENT_DELIVERY_MODE)
+        .with_headers(headers)
+}
+
+fn watch_connection(
+    connection: Weak<RabbitConnection>, generation: u64, events: impl Stream<Item = lapin::Event> + Send + 'static,
+) {
+    tokio::spawn(async move {
+        let mut events = Box::pin(events);
+
+        while let Some(event) = events.next().await {
+            if let lapin::Event::Error(err) = event {
+                let Some(strong) = connection.upgrade() else {
+                    return;
+                };
+
+                log::error!("RabbitMQ connection lost: {}", err);
+
+                if strong.invalidate(generation).await {
+                    drop(strong);
+                    reconnect(connection).await;
+                }
+                return;
+            }
+        }
+    });
+}
+
+// Keeps reconnecting in the background so an idle bus recovers without
+// waiting for the next publish, until it succeeds or the bus is dropped.
+async fn reconnect(connection: Weak<RabbitConnection>) {
+    let mut round = 1;
+    loop {
+        let Some(strong) = connection.upgrade() else {
+            return;
+        };
+
+        if strong.channel().await.is_ok() {
+            return;
+        }
+
+        let backoff = strong.reconnect_policy.backoff(round).max(MIN_RECONNECT_BACKOFF);
+        drop(strong);
+
+        log::warn!("RabbitMQ still unreachable, retrying in {:?}", backoff);
+        tokio::time::sleep(backoff).await;
+        round += 1;
+    }
+}
+
+impl RabbitConnection {
+    async fn connect(self: &Arc<Self>) -> Result<(u64, Channel), lapin::Error> {
+        let conn = Connection::connect(&self.amqp_url, ConnectionProperties::default()).await?;
         let channel = conn.create_channel().await?;
 
         channel
             .exchange_declare(
-                &exchange,
+                &self.exchange,
                 lapin::ExchangeKind::Topic,
                 ExchangeDeclareOptions::default(),
                 FieldTable::default(),
             )
             .await?;
 
-        Ok(Self {
-            channel,
+        channel.confirm_select(ConfirmSelectOptions::default()).await?;
+
+        let generation = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        watch_connection(Arc::downgrade(self), generation, conn.events_listener());
+
+        *self.connection.lock().await = Some((generation, conn, channel.clone()));
+
+        Ok((generation, channel))
+    }
+
+    async fn channel(self: &Arc<Self>) -> Result<(u64, Channel), lapin::Error> {
+        if let Some(channel) = self.healthy_channel().await {
+            return Ok(channel);
+        }
+
+        let _reconnecting = self.reconnecting.lock().await;
+        if let Some(channel) = self.healthy_channel().await {
+            return Ok(channel);
+        }
+
+        self.state.send_replace(ConnectionState::Reconnecting);
+
+        let mut attempt = 1;
+        loop {
+            log::info!("Connecting to RabbitMQ (attempt {})", attempt);
+
+            match self.connect().await {
+                Ok(channel) => {
+                    log::info!("Connected to RabbitMQ exchange: {}", self.exchange);
+
+                    self.state.send_replace(ConnectionState::Connected);
+                    return Ok(channel);
+                },
+                Err(err) if !self.reconnect_policy.should_retry(attempt) => {
+                    log::error!("Giving up connecting to RabbitMQ after {} attempts: {}", attempt, err);
+
+                    self.state.send_replace(ConnectionState::Disconnected);
+                    return Err(err);
+                },
+                Err(err) => {
+                    log::warn!("Failed to connect to RabbitMQ: {}", err);
+                },
+            }
+
+            tokio::time::sleep(self.reconnect_policy.backoff(attempt)).await;
+            attempt += 1;
+        }
+    }
+
+    async fn healthy_channel(&self) -> Option<(u64, Channel)> {
+        match self.connection.lock().await.as_ref() {
+            Some((generation, conn, channel)) if conn.status().connected() && channel.status().connected() => {
+                Some((*generation, channel.clone()))
+            },
+            _ => None,
+        }
+    }
+
+    async fn invalidate(&self, generation: u64) -> bool {
+        let mut connection = self.connection.lock().await;
+        if !matches!(connection.as_ref(), Some((current, ..)) if *current == generation) {
+            log::debug!("Ignoring failure of stale RabbitMQ connection {}", generation);
+            return false;
+        }
+
+        *connection = None;
+        self.state.send_replace(ConnectionState::Disconnected);
+        true
+    }
+}
+
+impl RabbitEventBus {
+    pub async fn new(amqp_url: &str, exchange: String) -> Result<Self, lapin::Error> {
+        Self::with_reconnect_policy(
+            amqp_url,
+            exchange,
+            RetryPolicy::new(5).with_backoff(Duration::from_millis(100), Duration::from_secs(10)),
+        )
+        .await
+    }
+
+    pub async fn with_reconnect_policy(
+        amqp_url: &str, exchange: String, reconnect_policy: RetryPolicy,
+    ) -> Result<Self, lapin::Error> {
+        let (state, _) = watch::channel(ConnectionState::Disconnected);
+        let inner = Arc::new(RabbitConnection {
+            amqp_url: amqp_url.to_string(),
             exchange,
+            connection: Mutex::new(None),
+            generation: AtomicU64::new(0),
+            reconnecting: Mutex::new(()),
+            state,
+            reconnect_policy,
+        });
+
+        inner.connect().await?;
+        inner.state.send_replace(ConnectionState::Connected);
+
+        Ok(Self {
+            inner,
         })
     }
+
+    pub fn state(&self) -> ConnectionState { *self.inner.state.borrow() }
+
+    pub fn is_healthy(&self) -> bool { self.state() == ConnectionState::Connected }
+
+    pub fn watch_state(&self) -> watch::Receiver<ConnectionState> { self.inner.state.subscribe() }
+
+    async fn publish_batch<E: Event>(
+        &self, channel: &Channel, events: &[EventEnvelope<E>], payloads: &[Vec<u8>],
+    ) -> Result<(), RabbitError> {
+        let mut confirms = Vec::with_capacity(events.len());
+        for (event, payload) in events.iter().zip(payloads) {
+            let routing_key = format!("events.{}", event.event.event_type());
+
+            let confirm = channel
+                .basic_publish(
+                    &self.inner.exchange,
+                    &routing_key,
+                    BasicPublishOptions {
+                        mandatory: true,
+                        ..BasicPublishOptions::default()
+                    },
+                    payload,
+                    message_properties(event),
+                )
+                .await?;
+
+            log::debug!("Published event: {}", event.event.event_type());
+
+            confirms.push((event.metadata.event_id, routing_key, confirm));
+        }
+
+        for (event_id, routing_key, confirm) in confirms {
+            match confirm.await? {
+                Confirmation::Ack(None) | Confirmation::NotRequested => {},
+                Confirmation::Ack(Some(returned)) => {
+                    return Err(RabbitError::Returned {
+                        event_id,
+                        routing_key,
+                        reply_code: returned.reply_code,
+                        reply_text: returned.reply_text.to_string(),
+                    });
+                },
+                Confirmation::Nack(_) => {
+                    return Err(RabbitError::Nacked {
+                        event_id,
+                        routing_key,
+                    });
+                },
+            }
+        }
+
+        Ok(())
+    }
 }
 
 #[async_trait]
 impl<E: Event + Serialize + Send + Sync> EventBus<E> for RabbitEventBus {
-    type Error = lapin::Error;
+    type Error = RabbitError;
 
     async fn publish(&self, events: &[EventEnvelope<E>]) -> Result<(), Self::Error> {
         log::info!(
             "Publishing {} events to RabbitMQ exchange: {}",
             events.len(),
-            self.exchange
+            self.inner.exchange
         );
 
-        for event in events {
-            let payload = serde_json::to_string(event).unwrap().into_bytes();
-            let routing_key = format!("events.{}", event.event.event_type());
+        let payloads = events.iter().map(serde_json::to_vec).collect::<Result<Vec<_>, _>>()?;
 
-            self.channel
-                .basic_publish(
-                    &self.exchange,
-                    &routing_key,
-                    BasicPublishOptions::default(),
-                    &payload,
-                    BasicProperties::default(),
-                )
-                .await?;
+        let mut attempt = 1;
+        loop {
+            let (generation, channel) = self.inner.channel().await?;
 
-            log::debug!("Published event: {}", event.event.event_type());
+            match self.publish_batch(&channel, events, &payloads).await {
+                Err(RabbitError::Lapin(err)) if self.inner.reconnect_policy.should_retry(attempt) => {
+                    log::warn!("Publish failed on attempt {}, reconnecting: {}", attempt, err);
+
+                    self.inner.invalidate(generation).await;
+                    tokio::time::sleep(self.inner.reconnect_policy.backoff(attempt)).await;
+                    attempt += 1;
+                },
+                result => {
+                    if result.is_ok() {
+                        log::debug!("Broker confirmed {} events", events.len());
+                    }
+                    return result;
+                },
+            }
         }
+    }
 
-        Ok(())
+    fn subscribe<H: crate::EventHandler<E> + Send + Sync + 'static>(&mut self, _handler: H)
+    where
+        H::Error: std::error::Error + Send + Sync + 'static,
+    {
+        log::warn!("RabbitMQ subscribe not supported on the publisher - use RabbitEventConsumer");
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EventMetadata;
+
+    #[derive(Debug, Clone, Serialize)]
+    struct Pinged;
+
+    impl Event for Pinged {
+        fn event_type(&self) -> &'static str { "Pinged" }
+    }
+
+    fn header(properties: &BasicProperties, key: &str) -> Option<String> {
+        match properties.headers().as_ref()?.inner().get(key)? {
+            AMQPValue::LongString(value) => Some(value.to_string()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn custom_headers_are_sent_and_reserved_keys_win() {
+        let causation_id = Uuid::new_v4();
+        let envelope = EventEnvelope {
+            event: Pinged,
+            metadata: EventMetadata::new(Uuid::new_v4(), Some(causation_id))
+                .with_header("trace", "abc")
+                .with_header("event_type", "Spoofed")
+                .with_header("causation_id", "spoofed"),
+        };
+
+        let properties = message_properties(&envelope);
 
-    fn subscribe<H: crate::EventHandler<E> + Send + Sync + 'static>(&mut self, _handler: H) {
-        log::warn!("RabbitMQ subscribe not implemented - use separate consumer");
+        assert_eq!(header(&properties, "trace").as_deref(), Some("abc"));
+        assert_eq!(header(&properties, "event_type").as_deref(), Some("Pinged"));
+        assert_eq!(header(&properties, "causation_id"), Some(causation_id.to_string()));
     }
 }
@@ -0,0 +1,473 @@
+use std::collections::BTreeSet;
+use std::time::Duration;
+
+use futures_util::StreamExt;
+use lapin::message::Delivery;
+use lapin::options::*;
+use lapin::types::{AMQPValue, FieldTable};
+use lapin::{BasicProperties, Channel, Connection, ConnectionProperties};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::event_bus::{ErasedEventHandler, handle_with_retry};
+use crate::{Event, EventEnvelope, EventFilter, EventHandler, RetryPolicy};
+
+const RETRY_COUNT_HEADER: &str = "x-retry-count";
+
+pub struct RabbitEventConsumer<E: Event> {
+    channel: Channel,
+    exchange: String,
+    queue: String,
+    routing_keys: Vec<String>,
+    prefetch_count: u16,
+    retry_policy: RetryPolicy,
+    dead_letter_exchange: Option<String>,
+    requeue_delay: Duration,
+    max_redeliveries: u32,
+    propagated_headers: BTreeSet<String>,
+    handlers: Vec<(Box<dyn ErasedEventHandler<E>>, EventFilter)>,
+}
+
+impl<E: Event + for<'de> Deserialize<'de> + 'static> RabbitEventConsumer<E> {
+    pub async fn new(amqp_url: &str, exchange: String, queue: String) -> Result<Self, lapin::Error> {
+        let conn = Connection::connect(amqp_url, ConnectionProperties::default()).await?;
+        let channel = conn.create_channel().await?;
+
+        channel
+            .exchange_declare(
+                &exchange,
+                lapin::ExchangeKind::Topic,
+                ExchangeDeclareOptions::default(),
+                FieldTable::default(),
+            )
+            .await?;
+
+        channel.confirm_select(ConfirmSelectOptions::default()).await?;
+
+        Ok(Self {
+            channel,
+            exchange,
+            queue,
+            routing_keys: Vec::new(),
+            prefetch_count: 10,
+            retry_policy: RetryPolicy::none(),
+            dead_letter_exchange: None,
+            requeue_delay: Duration::from_secs(1),
+            max_redeliveries: 5,
+            propagated_headers: BTreeSet::new(),
+            handlers: Vec::new(),
+        })
+    }
+
+    pub fn bind_event_type(mut self, event_type: &str) -> Self {
+        self.routing_keys.push(format!("events.{}", event_type));
+        self
+    }
+
+    pub fn with_prefetch_count(mut self, prefetch_count: u16) -> Self {
+        self.prefetch_count = prefetch_count;
+        self
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    pub fn with_dead_letter_exchange(mut self, dead_letter_exchange: String) -> Self {
+        self.dead_letter_exchange = Some(dead_letter_exchange);
+        self
+    }
+
+    pub fn with_requeue_delay(mut self, requeue_delay: Duration) -> Self {
+        self.requeue_delay = requeue_delay;
+        self
+    }
+
+    pub fn with_max_redeliveries(mut self, max_redeliveries: u32) -> Self {
+        self.max_redeliveries = max_redeliveries;
+        self
+    }
+
+    pub fn with_propagated_headers<K: Into<String>>(mut self, keys: impl IntoIterator<Item = K>) -> Self {
+        self.propagated_headers.extend(keys.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn dead_letter_queue(&self) -> Option<String> {
+        self.dead_letter_exchange
+            .as_ref()
+            .map(|_| format!("{}.dead-letter", self.queue))
+    }
+
+    pub fn subscribe<H: EventHandler<E> + Send + Sync + 'static>(&mut self, handler: H)
+    where
+        H::Error: std::error::Error + Send + Sync + 'static,
+    {
+        self.subscribe_filtered(handler, EventFilter::all());
+    }
+
+    pub fn subscribe_filtered<H: EventHandler<E> + Send + Sync + 'static>(&mut self, handler: H, filter: EventFilter)
+    where
+        H::Error: std::error::Error + Send + Sync + 'static,
+    {
+        log::info!(
+            "Registering RabbitMQ event handler (total: {})",
+            self.handlers.len() + 1
+        );
+
+        self.handlers.push((Box::new(handler), filter));
+    }
+
+    fn binding_keys(&self) -> BTreeSet<String> {
+        if !self.routing_keys.is_empty() {
+            return self.routing_keys.iter().cloned().collect();
+        }
+
+        let routing_keys: BTreeSet<String> = self
+            .handlers
+            .iter()
+            .flat_map(|(_, filter)| filter.routing_keys())
+            .collect();
+
+        if routing_keys.is_empty() || routing_keys.contains("events.#") {
+            return BTreeSet::from(["events.#".to_string()]);
+        }
+        routing_keys
+    }
+
+    pub async fn run(&self) -> Result<(), lapin::Error> {
+        if let Some(dead_letter_exchange) = &self.dead_letter_exchange {
+            self.declare_dead_letter_queue(dead_letter_exchange).await?;
+        }
+
+        self.channel
+            .queue_declare(
+                &self.queue,
+                QueueDeclareOptions {
+                    durable: true,
+                    ..QueueDeclareOptions::default()
+                },
+                FieldTable::default(),
+            )
+            .await?;
+
+        for routing_key in self.binding_keys() {
+            log::info!(
+                "Binding queue {} to {} with key {}",
+                self.queue,
+                self.exchange,
+                routing_key
+            );
+
+            self.channel
+                .queue_bind(
+                    &self.queue,
+                    &self.exchange,
+                    &routing_key,
+                    QueueBindOptions::default(),
+                    FieldTable::default(),
+                )
+                .await?;
+        }
+
+        self.channel
+            .basic_qos(self.prefetch_count, BasicQosOptions::default())
+            .await?;
+
+        let mut consumer = self
+            .channel
+            .basic_consume(&self.queue, "", BasicConsumeOptions::default(), FieldTable::default())
+            .await?;
+
+        log::info!("Consuming events from queue: {}", self.queue);
+
+        while let Some(delivery) = consumer.next().await {
+            let delivery = delivery?;
+
+            let mut envelope: EventEnvelope<E> = match serde_json::from_slice(&delivery.data) {
+                Ok(envelope) => envelope,
+                Err(err) => {
+                    log::error!("Failed to deserialize event from {}: {}", delivery.routing_key, err);
+
+                    self.dead_letter(delivery).await?;
+                    continue;
+                },
+            };
+
+            if envelope.metadata.aggregate_type.is_none() {
+                envelope.metadata.aggregate_type = header(&delivery.properties, "aggregate_type");
+            }
+            if envelope.metadata.aggregate_id.is_none() {
+                envelope.metadata.aggregate_id = header(&delivery.properties, "aggregate_id");
+            }
+
+            if self.dispatch(&envelope).await {
+                delivery.acker.ack(BasicAckOptions::default()).await?;
+            } else if self.dead_letter_exchange.is_some() {
+                log::warn!("Dead-lettering event {}", envelope.metadata.event_id);
+
+                self.dead_letter(delivery).await?;
+            } else {
+                self.requeue(delivery, envelope.metadata.event_id).await?;
+            }
+        }
+
+        log::warn!("RabbitMQ consumer for queue {} stopped", self.queue);
+
+        Ok(())
+    }
+
+    async fn dispatch(&self, envelope: &EventEnvelope<E>) -> bool {
+        log::debug!("Processing event: {}", envelope.event.event_type());
+
+        for (i, (handler, filter)) in self.handlers.iter().enumerate() {
+            if !filter.matches(envelope) {
+                continue;
+            }
+
+            if let Err((attempts, err)) =
+                handle_with_retry(handler.as_ref(), &self.retry_policy, &self.propagated_headers, envelope).await
+            {
+                log::warn!(
+                    "Handler {} failed for event {} after {} attempts: {}",
+                    i,
+                    envelope.metadata.event_id,
+                    attempts,
+                    err
+                );
+                return false;
+            }
+        }
+
+        true
+    }
+
+    async fn dead_letter(&self, delivery: Delivery) -> Result<(), lapin::Error> {
+        let Some(dead_letter_exchange) = &self.dead_letter_exchange else {
+            log::error!(
+                "Discarding message from {} without a dead-letter exchange",
+                delivery.routing_key
+            );
+            delivery.acker.reject(BasicRejectOptions::default()).await?;
+            return Ok(());
+        };
+
+        let properties = delivery.properties.clone();
+        if publish_confirmed(
+            &self.channel,
+            dead_letter_exchange,
+            delivery.routing_key.as_str(),
+            &delivery.data,
+            properties,
+        )
+        .await?
+        {
+            delivery.acker.ack(BasicAckOptions::default()).await?;
+        } else {
+            log::error!("Broker rejected dead letter from {}, requeueing", delivery.routing_key);
+            delivery
+                .acker
+                .nack(BasicNackOptions {
+                    requeue: true,
+                    ..BasicNackOptions::default()
+                })
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    // Failed deliveries are republished with a bumped retry count once the
+    // delay has passed, so the consume loop keeps going in the meantime.
+    async fn requeue(&self, delivery: Delivery, event_id: Uuid) -> Result<(), lapin::Error> {
+        let retries = retry_count(&delivery.properties);
+        if retries >= self.max_redeliveries {
+            log::error!("Discarding event {} after {} redeliveries", event_id, retries);
+            delivery.acker.reject(BasicRejectOptions::default()).await?;
+            return Ok(());
+        }
+
+        log::warn!("Requeueing event {} in {:?}", event_id, self.requeue_delay);
+
+        let channel = self.channel.clone();
+        let queue = self.queue.clone();
+        let requeue_delay = self.requeue_delay;
+        tokio::spawn(async move {
+            tokio::time::sleep(requeue_delay).await;
+
+            let properties = with_retry_count(&delivery.properties, retries + 1);
+            let result = match publish_confirmed(&channel, "", &queue, &delivery.data, properties).await {
+                Ok(true) => delivery.acker.ack(BasicAckOptions::default()).await,
+                _ => {
+                    delivery
+                        .acker
+                        .nack(BasicNackOptions {
+                            requeue: true,
+                            ..BasicNackOptions::default()
+                        })
+                        .await
+                },
+            };
+            if let Err(err) = result {
+                log::error!("Failed to requeue event {}: {}", event_id, err);
+            }
+        });
+
+        Ok(())
+    }
+
+    async fn declare_dead_letter_queue(&self, dead_letter_exchange: &str) -> Result<(), lapin::Error> {
+        let dead_letter_queue = format!("{}.dead-letter", self.queue);
+
+        self.channel
+            .exchange_declare(
+                dead_letter_exchange,
+                lapin::ExchangeKind::Topic,
+                ExchangeDeclareOptions {
+                    durable: true,
+                    ..ExchangeDeclareOptions::default()
+                },
+                FieldTable::default(),
+            )
+            .await?;
+
+        self.channel
+            .queue_declare(
+                &dead_letter_queue,
+                QueueDeclareOptions {
+                    durable: true,
+                    ..QueueDeclareOptions::default()
+                },
+                FieldTable::default(),
+            )
+            .await?;
+
+        self.channel
+            .queue_bind(
+                &dead_letter_queue,
+                dead_letter_exchange,
+                "#",
+                QueueBindOptions::default(),
+                FieldTable::default(),
+            )
+            .await
+    }
+
+    pub async fn peek_dead_letters(&self, max: usize) -> Result<Vec<EventEnvelope<E>>, lapin::Error> {
+        let Some(dead_letter_queue) = self.dead_letter_queue() else {
+            return Ok(Vec::new());
+        };
+
+        let mut messages = Vec::new();
+        while messages.len() < max {
+            let Some(message) = self
+                .channel
+                .basic_get(&dead_letter_queue, BasicGetOptions::default())
+                .await?
+            else {
+                break;
+            };
+            messages.push(message.delivery);
+        }
+
+        let mut dead_letters = Vec::with_capacity(messages.len());
+        for delivery in messages {
+            match serde_json::from_slice(&delivery.data) {
+                Ok(envelope) => dead_letters.push(envelope),
+                Err(err) => log::warn!("Skipping undecodable dead letter: {}", err),
+            }
+
+            delivery
+                .acker
+                .nack(BasicNackOptions {
+                    requeue: true,
+                    ..BasicNackOptions::default()
+                })
+                .await?;
+        }
+
+        Ok(dead_letters)
+    }
+
+    pub async fn replay_dead_letters(&self, max: usize) -> Result<usize, lapin::Error> {
+        let Some(dead_letter_queue) = self.dead_letter_queue() else {
+            return Ok(0);
+        };
+
+        let mut replayed = 0;
+        while replayed < max {
+            let Some(message) = self
+                .channel
+                .basic_get(&dead_letter_queue, BasicGetOptions::default())
+                .await?
+            else {
+                break;
+            };
+            let delivery = message.delivery;
+
+            log::info!("Replaying dead letter with routing key {}", delivery.routing_key);
+
+            let properties = with_retry_count(&delivery.properties, 0);
+            if !publish_confirmed(&self.channel, "", &self.queue, &delivery.data, properties).await? {
+                log::error!(
+                    "Broker rejected replayed dead letter, leaving it in {}",
+                    dead_letter_queue
+                );
+                delivery
+                    .acker
+                    .nack(BasicNackOptions {
+                        requeue: true,
+                        ..BasicNackOptions::default()
+                    })
+                    .await?;
+                break;
+            }
+
+            delivery.acker.ack(BasicAckOptions::default()).await?;
+            replayed += 1;
+        }
+
+        Ok(replayed)
+    }
+}
+
+fn header(properties: &BasicProperties, key: &str) -> Option<String> {
+    match properties.headers().as_ref()?.inner().get(key)? {
+        AMQPValue::LongString(value) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+fn retry_count(properties: &BasicProperties) -> u32 {
+    match properties
+        .headers()
+        .as_ref()
+        .and_then(|headers| headers.inner().get(RETRY_COUNT_HEADER))
+    {
+        Some(AMQPValue::LongUInt(count)) => *count,
+        _ => 0,
+    }
+}
+
+fn with_retry_count(properties: &BasicProperties, count: u32) -> BasicProperties {
+    let mut headers = properties.headers().clone().unwrap_or_default();
+    headers.insert(RETRY_COUNT_HEADER.into(), AMQPValue::LongUInt(count));
+    properties.clone().with_headers(headers)
+}
+
+async fn publish_confirmed(
+    channel: &Channel, exchange: &str, routing_key: &str, payload: &[u8], properties: BasicProperties,
+) -> Result<bool, lapin::Error> {
+    let confirmation = channel
+        .basic_publish(
+            exchange,
+            routing_key,
+            BasicPublishOptions::default(),
+            payload,
+            properties,
+        )
+        .await?
+        .await?;
+    Ok(confirmation.is_ack())
+}
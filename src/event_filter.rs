@@ -0,0 +1,78 @@
+use std::collections::HashSet;
+
+use crate::{Event, EventEnvelope};
+
+#[derive(Debug, Clone, Default)]
+pub struct EventFilter {
+    event_types: Option<HashSet<String>>,
+    aggregate_types: Option<HashSet<String>>,
+    aggregate_ids: Option<HashSet<String>>,
+}
+
+impl EventFilter {
+    pub fn all() -> Self { Self::default() }
+
+    pub fn event_types<T: Into<String>>(event_types: impl IntoIterator<Item = T>) -> Self {
+        Self::all().with_event_types(event_types)
+    }
+
+    pub fn with_event_types<T: Into<String>>(mut self, event_types: impl IntoIterator<Item = T>) -> Self {
+        self.event_types
+            .get_or_insert_with(HashSet::new)
+            .extend(event_types.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn with_aggregate_types<T: Into<String>>(mut self, aggregate_types: impl IntoIterator<Item = T>) -> Self {
+        self.aggregate_types
+            .get_or_insert_with(HashSet::new)
+            .extend(aggregate_types.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn with_aggregate_ids<T: Into<String>>(mut self, aggregate_ids: impl IntoIterator<Item = T>) -> Self {
+        self.aggregate_ids
+            .get_or_insert_with(HashSet::new)
+            .extend(aggregate_ids.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn matches<E: Event>(&self, envelope: &EventEnvelope<E>) -> bool {
+        let type_matches = self
+            .event_types
+            .as_ref()
+            .is_none_or(|event_types| event_types.contains(envelope.event.event_type()));
+
+        let aggregate_type_matches = self.aggregate_types.as_ref().is_none_or(|aggregate_types| {
+            envelope
+                .metadata
+                .aggregate_type
+                .as_ref()
+                .is_some_and(|aggregate_type| aggregate_types.contains(aggregate_type))
+        });
+
+        // Stores and the RabbitMQ consumer backfill aggregate_type and aggregate_id
+        // from the stream; envelopes built by hand without them never match.
+        let aggregate_matches = self.aggregate_ids.as_ref().is_none_or(|aggregate_ids| {
+            envelope
+                .metadata
+                .aggregate_id
+                .as_ref()
+                .is_some_and(|aggregate_id| aggregate_ids.contains(aggregate_id))
+        });
+
+        type_matches && aggregate_type_matches && aggregate_matches
+    }
+
+    pub fn routing_keys(&self) -> Vec<String> {
+        match &self.event_types {
+            Some(event_types) => {
+                event_types
+                    .iter()
+                    .map(|event_type| format!("events.{}", event_type))
+                    .collect()
+            },
+            None => vec!["events.#".to_string()],
+        }
+    }
+}
@@ -1,8 +1,10 @@
+use std::collections::BTreeMap;
+
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::Event;
+use crate::{Event, MessageContext};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EventMetadata {
@@ -10,6 +12,16 @@ pub struct EventMetadata {
     pub timestamp: DateTime<Utc>,
     pub correlation_id: Uuid,
     pub causation_id: Option<Uuid>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aggregate_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aggregate_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant_id: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
 }
 
 impl EventMetadata {
@@ -19,8 +31,39 @@ impl EventMetadata {
             timestamp: Utc::now(),
             correlation_id,
             causation_id,
+            aggregate_type: None,
+            aggregate_id: None,
+            user_id: None,
+            tenant_id: None,
+            headers: BTreeMap::new(),
+        }
+    }
+
+    pub fn from_context(context: &MessageContext) -> Self {
+        Self {
+            user_id: context.user_id.clone(),
+            tenant_id: context.tenant_id.clone(),
+            headers: context.headers.clone(),
+            ..Self::new(context.correlation_id, context.causation_id)
         }
     }
+
+    pub fn with_aggregate_type(mut self, aggregate_type: impl Into<String>) -> Self {
+        self.aggregate_type = Some(aggregate_type.into());
+        self
+    }
+
+    pub fn with_aggregate_id(mut self, aggregate_id: impl Into<String>) -> Self {
+        self.aggregate_id = Some(aggregate_id.into());
+        self
+    }
+
+    pub fn with_header(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.insert(key.into(), value.into());
+        self
+    }
+
+    pub fn header(&self, key: &str) -> Option<&str> { self.headers.get(key).map(String::as_str) }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
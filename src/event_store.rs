@@ -1,10 +1,14 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::RwLock;
+
 use async_trait::async_trait;
 
 use crate::{Event, EventEnvelope};
 
 #[async_trait]
 pub trait EventStore<E: Event, Id> {
-    type Error;
+    type Error: 'static;
 
     async fn save_events(
         &self, aggregate_id: &Id, events: Vec<EventEnvelope<E>>, expected_version: u64,
@@ -15,4 +19,165 @@ pub trait EventStore<E: Event, Id> {
     async fn get_events_from_version(
         &self, aggregate_id: &Id, from_version: u64,
     ) -> Result<Vec<EventEnvelope<E>>, Self::Error>;
+
+    async fn read_all(&self, from_position: u64, batch_size: usize) -> Result<Vec<StoredEvent<E, Id>>, Self::Error>;
+
+    async fn get_stream_from_version(
+        &self, aggregate_id: &Id, from_version: u64,
+    ) -> Result<Vec<(u64, EventEnvelope<E>)>, Self::Error>
+    where
+        Self: Sync,
+        Id: Sync,
+    {
+        let events = self.get_events_from_version(aggregate_id, from_version).await?;
+
+        Ok(events
+            .into_iter()
+            .zip(from_version + 1..)
+            .map(|(envelope, version)| (version, envelope))
+            .collect())
+    }
+
+    fn uses_outbox(&self) -> bool { false }
+
+    fn aggregate_type(&self) -> Option<&str> { None }
+}
+
+#[derive(Debug, Clone)]
+pub struct StoredEvent<E: Event, Id> {
+    pub position: u64,
+    pub aggregate_id: Id,
+    pub version: u64,
+    pub envelope: EventEnvelope<E>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AggregateTypeMismatch {
+    pub expected: &'static str,
+    pub found: String,
+}
+
+impl std::fmt::Display for AggregateTypeMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Event store is scoped to aggregate type {}, expected {}",
+            self.found, self.expected
+        )
+    }
+}
+
+impl std::error::Error for AggregateTypeMismatch {}
+
+pub trait EventStoreError: std::error::Error + Send + Sync + 'static {
+    fn is_concurrency_conflict(&self) -> bool;
+}
+
+#[derive(Debug)]
+pub enum InMemoryEventStoreError {
+    ConcurrencyConflict,
+}
+
+impl std::fmt::Display for InMemoryEventStoreError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InMemoryEventStoreError::ConcurrencyConflict => write!(f, "Concurrency conflict"),
+        }
+    }
+}
+
+impl std::error::Error for InMemoryEventStoreError {}
+
+impl EventStoreError for InMemoryEventStoreError {
+    fn is_concurrency_conflict(&self) -> bool { matches!(self, InMemoryEventStoreError::ConcurrencyConflict) }
+}
+
+pub struct InMemoryEventStore<E: Event, Id> {
+    state: RwLock<InMemoryState<E, Id>>,
+}
+
+struct InMemoryState<E: Event, Id> {
+    log: Vec<StoredEvent<E, Id>>,
+    streams: HashMap<Id, Vec<usize>>,
+}
+
+impl<E: Event, Id: Eq + Hash> Default for InMemoryEventStore<E, Id> {
+    fn default() -> Self { Self::new() }
+}
+
+impl<E: Event, Id: Eq + Hash> InMemoryEventStore<E, Id> {
+    pub fn new() -> Self {
+        Self {
+            state: RwLock::new(InMemoryState {
+                log: Vec::new(),
+                streams: HashMap::new(),
+            }),
+        }
+    }
+}
+
+#[async_trait]
+impl<E: Event, Id: Eq + Hash + Clone + Send + Sync> EventStore<E, Id> for InMemoryEventStore<E, Id> {
+    type Error = InMemoryEventStoreError;
+
+    async fn save_events(
+        &self, aggregate_id: &Id, events: Vec<EventEnvelope<E>>, expected_version: u64,
+    ) -> Result<(), Self::Error> {
+        let mut state = self.state.write().unwrap();
+        let InMemoryState {
+            log,
+            streams,
+        } = &mut *state;
+        let stream = streams.entry(aggregate_id.clone()).or_default();
+
+        if stream.len() as u64 != expected_version {
+            return Err(InMemoryEventStoreError::ConcurrencyConflict);
+        }
+
+        for envelope in events {
+            stream.push(log.len());
+            log.push(StoredEvent {
+                position: log.len() as u64 + 1,
+                aggregate_id: aggregate_id.clone(),
+                version: stream.len() as u64,
+                envelope,
+            });
+        }
+
+        Ok(())
+    }
+
+    async fn get_events(&self, aggregate_id: &Id) -> Result<Vec<EventEnvelope<E>>, Self::Error> {
+        self.get_events_from_version(aggregate_id, 0).await
+    }
+
+    async fn get_events_from_version(
+        &self, aggregate_id: &Id, from_version: u64,
+    ) -> Result<Vec<EventEnvelope<E>>, Self::Error> {
+        let state = self.state.read().unwrap();
+
+        Ok(state
+            .streams
+            .get(aggregate_id)
+            .map(|stream| {
+                stream
+                    .iter()
+                    .skip(from_version as usize)
+                    .map(|&index| state.log[index].envelope.clone())
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    async fn read_all(&self, from_position: u64, batch_size: usize) -> Result<Vec<StoredEvent<E, Id>>, Self::Error> {
+        let state = self.state.read().unwrap();
+
+        Ok(state
+            .log
+            .iter()
+            .skip(from_position as usize)
+            .take(batch_size)
+            .cloned()
+            .collect())
+    }
 }
@@ -1,12 +1,32 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgRow;
 use sqlx::{PgPool, Row};
+use uuid::Uuid;
+
+use crate::{
+    Aggregate, AggregateId, Event, EventEnvelope, EventMetadata, EventStore, EventStoreError, RawEvent, StoredEvent,
+    UpcastError, UpcasterRegistry,
+};
 
-use crate::{Event, EventEnvelope, EventMetadata, EventStore};
+// Appends take one database-wide advisory lock so that `position` values become
+// visible in commit order. Without it, a catch-up reader could checkpoint past
+// a lower position whose transaction commits later and never see that event.
+// The price is that all appends are serialized, across every aggregate. The
+// lock is advisory and only honoured by this store, so the unique (aggregate,
+// version) index stays as the backstop for writers that bypass it.
+const APPEND_LOCK_KEY: i64 = 0x6371_7273_6576_6e74;
 
 #[derive(Clone)]
 pub struct PostgresEventStore {
     pub(crate) pool: PgPool,
+    outbox: bool,
+    upcasters: Arc<UpcasterRegistry>,
+    aggregate_type: Option<String>,
+    legacy_aggregate_types: BTreeMap<String, String>,
 }
 
 #[derive(Debug)]
@@ -14,6 +34,10 @@ pub enum PostgresError {
     Sqlx(sqlx::Error),
     Serialization(serde_json::Error),
     ConcurrencyConflict,
+    AggregateId(Box<dyn std::error::Error + Send + Sync>),
+    Upcast(UpcastError),
+    MissingAggregateType,
+    UntypedEvents(i64),
 }
 
 impl From<sqlx::Error> for PostgresError {
@@ -24,30 +48,239 @@ impl From<serde_json::Error> for PostgresError {
     fn from(err: serde_json::Error) -> Self { PostgresError::Serialization(err) }
 }
 
+impl From<UpcastError> for PostgresError {
+    fn from(err: UpcastError) -> Self { PostgresError::Upcast(err) }
+}
+
+impl std::fmt::Display for PostgresError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PostgresError::Sqlx(err) => write!(f, "Database error: {}", err),
+            PostgresError::Serialization(err) => write!(f, "Serialization error: {}", err),
+            PostgresError::ConcurrencyConflict => write!(f, "Concurrency conflict"),
+            PostgresError::AggregateId(err) => write!(f, "Invalid aggregate id: {}", err),
+            PostgresError::Upcast(err) => write!(f, "Upcast error: {}", err),
+            PostgresError::MissingAggregateType => {
+                write!(
+                    f,
+                    "Event store is not scoped to an aggregate type, call for_aggregate first"
+                )
+            },
+            PostgresError::UntypedEvents(count) => {
+                write!(
+                    f,
+                    "{} events have no aggregate type, map their event types with with_legacy_aggregate_type",
+                    count
+                )
+            },
+        }
+    }
+}
+
+impl std::error::Error for PostgresError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PostgresError::Sqlx(err) => Some(err),
+            PostgresError::Serialization(err) => Some(err),
+            PostgresError::ConcurrencyConflict => None,
+            PostgresError::AggregateId(err) => Some(err.as_ref()),
+            PostgresError::Upcast(err) => Some(err),
+            PostgresError::MissingAggregateType | PostgresError::UntypedEvents(_) => None,
+        }
+    }
+}
+
+impl EventStoreError for PostgresError {
+    fn is_concurrency_conflict(&self) -> bool { matches!(self, PostgresError::ConcurrencyConflict) }
+}
+
+impl PostgresError {
+    fn from_append_error(err: sqlx::Error) -> Self {
+        match err {
+            sqlx::Error::Database(ref db_err) if db_err.is_unique_violation() => PostgresError::ConcurrencyConflict,
+            err => PostgresError::Sqlx(err),
+        }
+    }
+}
+
+pub(crate) fn decode_envelopes<E: Event + for<'de> Deserialize<'de>>(
+    upcasters: &UpcasterRegistry, raw: RawEvent, metadata: EventMetadata,
+) -> Result<Vec<EventEnvelope<E>>, PostgresError> {
+    let upcasted = upcasters.upcast(raw)?;
+    let split = upcasted.len() > 1;
+
+    upcasted
+        .into_iter()
+        .enumerate()
+        .map(|(index, raw)| {
+            let mut metadata = metadata.clone();
+            if split {
+                metadata.event_id = Uuid::new_v5(&metadata.event_id, &(index as u32).to_be_bytes());
+            }
+
+            Ok(EventEnvelope {
+                event: serde_json::from_value(raw.data)?,
+                metadata,
+            })
+        })
+        .collect()
+}
+
 impl PostgresEventStore {
     pub fn new(pool: PgPool) -> Self {
         Self {
             pool,
+            outbox: false,
+            upcasters: Arc::new(UpcasterRegistry::new()),
+            aggregate_type: None,
+            legacy_aggregate_types: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_outbox(mut self) -> Self {
+        self.outbox = true;
+        self
+    }
+
+    pub fn with_upcasters(mut self, upcasters: UpcasterRegistry) -> Self {
+        self.upcasters = Arc::new(upcasters);
+        self
+    }
+
+    pub fn for_aggregate<A: Aggregate>(mut self) -> Self {
+        self.aggregate_type = Some(A::aggregate_type().to_string());
+        self
+    }
+
+    pub fn with_legacy_aggregate_type(mut self, event_type: &str, aggregate_type: &str) -> Self {
+        self.legacy_aggregate_types
+            .insert(event_type.to_string(), aggregate_type.to_string());
+        self
+    }
+
+    pub fn upcasters(&self) -> Arc<UpcasterRegistry> { self.upcasters.clone() }
+
+    fn scope(&self) -> Result<&str, PostgresError> {
+        self.aggregate_type
+            .as_deref()
+            .ok_or(PostgresError::MissingAggregateType)
+    }
+
+    fn envelopes<E: Event + for<'de> Deserialize<'de>>(
+        &self, row: &PgRow,
+    ) -> Result<Vec<EventEnvelope<E>>, PostgresError> {
+        let mut metadata: EventMetadata = serde_json::from_value(row.get("metadata"))?;
+        if metadata.aggregate_type.is_none() {
+            metadata.aggregate_type = Some(row.get("aggregate_type"));
+        }
+        if metadata.aggregate_id.is_none() {
+            metadata.aggregate_id = Some(row.get("aggregate_id"));
+        }
+        let raw = RawEvent {
+            event_type: row.get("event_type"),
+            schema_version: row.get::<i32, _>("schema_version") as u32,
+            data: row.get("event_data"),
+        };
+
+        decode_envelopes(&self.upcasters, raw, metadata)
+    }
+
+    fn stored_batch<E: Event + for<'de> Deserialize<'de>, Id: AggregateId>(
+        &self, rows: Vec<PgRow>, batch_size: usize,
+    ) -> Result<Vec<StoredEvent<E, Id>>, PostgresError> {
+        let mut events = Vec::new();
+        for row in rows {
+            let stored = self.stored_events(&row)?;
+            // A row is never split across batches, or its checkpointed position would skip
+            // the rest.
+            if !events.is_empty() && events.len() + stored.len() > batch_size {
+                break;
+            }
+            events.extend(stored);
         }
+
+        Ok(events)
+    }
+
+    fn stored_events<E: Event + for<'de> Deserialize<'de>, Id: AggregateId>(
+        &self, row: &PgRow,
+    ) -> Result<Vec<StoredEvent<E, Id>>, PostgresError> {
+        let position = row.get::<i64, _>("position") as u64;
+        let aggregate_id = Id::decode(row.get("aggregate_id")).map_err(PostgresError::AggregateId)?;
+        let version = row.get::<i64, _>("version") as u64;
+
+        Ok(self
+            .envelopes(row)?
+            .into_iter()
+            .map(|envelope| {
+                StoredEvent {
+                    position,
+                    aggregate_id: aggregate_id.clone(),
+                    version,
+                    envelope,
+                }
+            })
+            .collect())
+    }
+
+    pub async fn aggregate_ids<Id: AggregateId>(&self) -> Result<Vec<Id>, PostgresError> {
+        let ids: Vec<String> = sqlx::query_scalar(
+            "SELECT DISTINCT aggregate_id FROM events WHERE aggregate_type = $1 ORDER BY aggregate_id",
+        )
+        .bind(self.scope()?)
+        .fetch_all(&self.pool)
+        .await?;
+
+        ids.iter()
+            .map(|id| Id::decode(id).map_err(PostgresError::AggregateId))
+            .collect()
+    }
+
+    pub async fn find_by_header<E: Event + for<'de> Deserialize<'de>, Id: AggregateId>(
+        &self, key: &str, value: &str, from_position: u64, batch_size: usize,
+    ) -> Result<Vec<StoredEvent<E, Id>>, PostgresError> {
+        let rows = sqlx::query(
+            "SELECT position, aggregate_type, aggregate_id, version, event_type, schema_version, event_data, metadata \
+             FROM events WHERE metadata->'headers' @> jsonb_build_object($1::text, $2::text) AND position > $3 AND \
+             ($5::text IS NULL OR aggregate_type = $5) ORDER BY position LIMIT $4",
+        )
+        .bind(key)
+        .bind(value)
+        .bind(from_position as i64)
+        .bind(batch_size as i64)
+        .bind(self.aggregate_type.as_deref())
+        .fetch_all(&self.pool)
+        .await?;
+
+        self.stored_batch(rows, batch_size)
     }
 }
 
 #[async_trait]
-impl<E: Event + Serialize + for<'de> Deserialize<'de> + Send + Sync + 'static> EventStore<E, String>
+impl<E: Event + Serialize + for<'de> Deserialize<'de> + Send + Sync + 'static, Id: AggregateId> EventStore<E, Id>
     for PostgresEventStore
 {
     type Error = PostgresError;
 
     async fn save_events(
-        &self, aggregate_id: &String, events: Vec<EventEnvelope<E>>, expected_version: u64,
+        &self, aggregate_id: &Id, events: Vec<EventEnvelope<E>>, expected_version: u64,
     ) -> Result<(), Self::Error> {
+        let aggregate_type = self.scope()?;
+        let aggregate_id = aggregate_id.encode();
         let mut tx = self.pool.begin().await?;
 
-        let current_version: Option<i64> =
-            sqlx::query_scalar("SELECT COALESCE(MAX(version), 0) FROM events WHERE aggregate_id = $1")
-                .bind(aggregate_id)
-                .fetch_one(&mut *tx)
-                .await?;
+        sqlx::query("SELECT pg_advisory_xact_lock($1)")
+            .bind(APPEND_LOCK_KEY)
+            .execute(&mut *tx)
+            .await?;
+
+        let current_version: Option<i64> = sqlx::query_scalar(
+            "SELECT COALESCE(MAX(version), 0) FROM events WHERE aggregate_type = $1 AND aggregate_id = $2",
+        )
+        .bind(aggregate_type)
+        .bind(&aggregate_id)
+        .fetch_one(&mut *tx)
+        .await?;
 
         if current_version.unwrap_or(0) != expected_version as i64 {
             return Err(PostgresError::ConcurrencyConflict);
@@ -59,64 +292,108 @@ impl<E: Event + Serialize + for<'de> Deserialize<'de> + Send + Sync + 'static> E
             let metadata = serde_json::to_value(&envelope.metadata)?;
 
             sqlx::query(
-                "INSERT INTO events (aggregate_id, event_type, event_data, metadata, version) VALUES ($1, $2, $3, $4, \
-                 $5)",
+                "INSERT INTO events (aggregate_type, aggregate_id, event_type, schema_version, event_data, metadata, \
+                 version) VALUES ($1, $2, $3, $4, $5, $6, $7)",
             )
-            .bind(aggregate_id)
+            .bind(aggregate_type)
+            .bind(&aggregate_id)
             .bind(envelope.event.event_type())
+            .bind(envelope.event.schema_version() as i32)
             .bind(event_data)
             .bind(metadata)
             .bind(version as i64)
             .execute(&mut *tx)
-            .await?;
+            .await
+            .map_err(PostgresError::from_append_error)?;
+
+            if self.outbox {
+                sqlx::query(
+                    "INSERT INTO outbox (event_id, aggregate_type, aggregate_id, event_type, schema_version, payload) \
+                     VALUES ($1, $2, $3, $4, $5, $6)",
+                )
+                .bind(envelope.metadata.event_id)
+                .bind(aggregate_type)
+                .bind(&aggregate_id)
+                .bind(envelope.event.event_type())
+                .bind(envelope.event.schema_version() as i32)
+                .bind(serde_json::to_value(envelope)?)
+                .execute(&mut *tx)
+                .await?;
+            }
         }
 
-        tx.commit().await?;
+        tx.commit().await.map_err(PostgresError::from_append_error)?;
         Ok(())
     }
 
-    async fn get_events(&self, aggregate_id: &String) -> Result<Vec<EventEnvelope<E>>, Self::Error> {
-        let rows = sqlx::query("SELECT event_data, metadata FROM events WHERE aggregate_id = $1 ORDER BY version")
-            .bind(aggregate_id)
-            .fetch_all(&self.pool)
-            .await?;
+    async fn get_events(&self, aggregate_id: &Id) -> Result<Vec<EventEnvelope<E>>, Self::Error> {
+        let rows = sqlx::query(
+            "SELECT aggregate_type, aggregate_id, event_type, schema_version, event_data, metadata FROM events WHERE \
+             aggregate_type = $1 AND aggregate_id = $2 ORDER BY version",
+        )
+        .bind(self.scope()?)
+        .bind(aggregate_id.encode())
+        .fetch_all(&self.pool)
+        .await?;
 
         let mut events = Vec::new();
         for row in rows {
-            let event: E = serde_json::from_value(row.get("event_data"))?;
-            let metadata: EventMetadata = serde_json::from_value(row.get("metadata"))?;
-            events.push(EventEnvelope {
-                event,
-                metadata,
-            });
+            events.extend(self.envelopes(&row)?);
         }
 
         Ok(events)
     }
 
     async fn get_events_from_version(
-        &self, aggregate_id: &String, from_version: u64,
+        &self, aggregate_id: &Id, from_version: u64,
     ) -> Result<Vec<EventEnvelope<E>>, Self::Error> {
+        Ok(self
+            .get_stream_from_version(aggregate_id, from_version)
+            .await?
+            .into_iter()
+            .map(|(_, envelope)| envelope)
+            .collect())
+    }
+
+    async fn get_stream_from_version(
+        &self, aggregate_id: &Id, from_version: u64,
+    ) -> Result<Vec<(u64, EventEnvelope<E>)>, Self::Error> {
         let rows = sqlx::query(
-            "SELECT event_data, metadata FROM events WHERE aggregate_id = $1 AND version > $2 ORDER BY version",
+            "SELECT aggregate_type, aggregate_id, version, event_type, schema_version, event_data, metadata FROM \
+             events WHERE aggregate_type = $1 AND aggregate_id = $2 AND version > $3 ORDER BY version",
         )
-        .bind(aggregate_id)
+        .bind(self.scope()?)
+        .bind(aggregate_id.encode())
         .bind(from_version as i64)
         .fetch_all(&self.pool)
         .await?;
 
         let mut events = Vec::new();
         for row in rows {
-            let event: E = serde_json::from_value(row.get("event_data"))?;
-            let metadata: EventMetadata = serde_json::from_value(row.get("metadata"))?;
-            events.push(EventEnvelope {
-                event,
-                metadata,
-            });
+            let version = row.get::<i64, _>("version") as u64;
+            events.extend(self.envelopes(&row)?.into_iter().map(|envelope| (version, envelope)));
         }
 
         Ok(events)
     }
+
+    async fn read_all(&self, from_position: u64, batch_size: usize) -> Result<Vec<StoredEvent<E, Id>>, Self::Error> {
+        let rows = sqlx::query(
+            "SELECT position, aggregate_type, aggregate_id, version, event_type, schema_version, event_data, metadata \
+             FROM events WHERE position > $1 AND ($3::text IS NULL OR aggregate_type = $3) ORDER BY position LIMIT $2",
+        )
+        .bind(from_position as i64)
+        .bind(batch_size as i64)
+        .bind(self.aggregate_type.as_deref())
+        .fetch_all(&self.pool)
+        .await?;
+
+        self.stored_batch(rows, batch_size)
+    }
+
+    fn uses_outbox(&self) -> bool { self.outbox }
+
+    fn aggregate_type(&self) -> Option<&str> { self.aggregate_type.as_deref() }
 }
 
 #[async_trait]
@@ -130,25 +407,220 @@ impl Migrator for PostgresEventStore {
         sqlx::query(
             "CREATE TABLE IF NOT EXISTS events (
             id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+            aggregate_type TEXT NOT NULL DEFAULT '',
             aggregate_id TEXT NOT NULL,
             event_type TEXT NOT NULL,
             event_data JSONB NOT NULL,
             metadata JSONB NOT NULL,
             version BIGINT NOT NULL,
+            schema_version INT NOT NULL DEFAULT 1,
+            position BIGSERIAL NOT NULL,
             created_at TIMESTAMPTZ DEFAULT NOW()
         )",
         )
         .execute(&self.pool)
         .await?;
 
+        // Existing rows get positions in commit order rather than heap order,
+        // which is what `ADD COLUMN ... BIGSERIAL` would assign them.
+        sqlx::query(
+            "DO $$
+            BEGIN
+                IF NOT EXISTS (
+                    SELECT 1 FROM information_schema.columns
+                    WHERE table_schema = current_schema() AND table_name = 'events' AND column_name = 'position'
+                ) THEN
+                    ALTER TABLE events ADD COLUMN position BIGINT;
+                    CREATE SEQUENCE IF NOT EXISTS events_position_seq OWNED BY events.position;
+                    UPDATE events SET position = ordered.position
+                    FROM (
+                        SELECT id, ROW_NUMBER() OVER (ORDER BY created_at, version, id) AS position FROM events
+                    ) AS ordered
+                    WHERE events.id = ordered.id;
+                    PERFORM setval('events_position_seq', COALESCE((SELECT MAX(position) FROM events), 0) + 1, false);
+                    ALTER TABLE events
+                        ALTER COLUMN position SET DEFAULT nextval('events_position_seq'),
+                        ALTER COLUMN position SET NOT NULL;
+                END IF;
+            END $$",
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query("ALTER TABLE events ADD COLUMN IF NOT EXISTS schema_version INT NOT NULL DEFAULT 1")
+            .execute(&self.pool)
+            .await?;
+
+        sqlx::query("CREATE UNIQUE INDEX IF NOT EXISTS idx_events_position ON events(position)")
+            .execute(&self.pool)
+            .await?;
+
         sqlx::query("CREATE INDEX IF NOT EXISTS idx_events_aggregate_id ON events(aggregate_id)")
             .execute(&self.pool)
             .await?;
 
-        sqlx::query("CREATE INDEX IF NOT EXISTS idx_events_version ON events(aggregate_id, version)")
+        sqlx::query("ALTER TABLE events ADD COLUMN IF NOT EXISTS aggregate_type TEXT NOT NULL DEFAULT ''")
+            .execute(&self.pool)
+            .await?;
+
+        sqlx::query("DROP INDEX IF EXISTS idx_events_version")
+            .execute(&self.pool)
+            .await?;
+
+        sqlx::query("DROP INDEX IF EXISTS idx_events_aggregate_version")
+            .execute(&self.pool)
+            .await?;
+
+        sqlx::query(
+            "CREATE UNIQUE INDEX IF NOT EXISTS idx_events_aggregate_type_version ON events(aggregate_type, \
+             aggregate_id, version)",
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_events_aggregate_type ON events(aggregate_type, position)")
+            .execute(&self.pool)
+            .await?;
+
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS idx_events_metadata_headers ON events USING GIN ((metadata->'headers'))",
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS snapshots (
+            aggregate_id TEXT NOT NULL,
+            version BIGINT NOT NULL,
+            snapshot_data JSONB NOT NULL,
+            created_at TIMESTAMPTZ DEFAULT NOW(),
+            PRIMARY KEY (aggregate_id, version)
+        )",
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query("ALTER TABLE snapshots ADD COLUMN IF NOT EXISTS aggregate_type TEXT NOT NULL DEFAULT ''")
+            .execute(&self.pool)
+            .await?;
+
+        sqlx::query("ALTER TABLE snapshots DROP CONSTRAINT IF EXISTS snapshots_pkey")
+            .execute(&self.pool)
+            .await?;
+
+        sqlx::query(
+            "CREATE UNIQUE INDEX IF NOT EXISTS idx_snapshots_aggregate_type_version ON snapshots(aggregate_type, \
+             aggregate_id, version)",
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS outbox (
+            id BIGSERIAL PRIMARY KEY,
+            event_id UUID NOT NULL,
+            aggregate_type TEXT NOT NULL DEFAULT '',
+            aggregate_id TEXT NOT NULL,
+            event_type TEXT NOT NULL,
+            schema_version INT NOT NULL DEFAULT 1,
+            payload JSONB NOT NULL,
+            attempts INT NOT NULL DEFAULT 0,
+            last_error TEXT,
+            created_at TIMESTAMPTZ DEFAULT NOW(),
+            locked_until TIMESTAMPTZ,
+            delivered_at TIMESTAMPTZ,
+            failed_at TIMESTAMPTZ
+        )",
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            "ALTER TABLE outbox ADD COLUMN IF NOT EXISTS aggregate_type TEXT NOT NULL DEFAULT '', ADD COLUMN IF NOT \
+             EXISTS schema_version INT NOT NULL DEFAULT 1, ADD COLUMN IF NOT EXISTS locked_until TIMESTAMPTZ, ADD \
+             COLUMN IF NOT EXISTS failed_at TIMESTAMPTZ",
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query("DROP INDEX IF EXISTS idx_outbox_pending")
             .execute(&self.pool)
             .await?;
 
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS idx_outbox_undelivered ON outbox(id) WHERE delivered_at IS NULL AND failed_at \
+             IS NULL",
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS checkpoints (
+            subscription TEXT PRIMARY KEY,
+            position BIGINT NOT NULL,
+            updated_at TIMESTAMPTZ DEFAULT NOW()
+        )",
+        )
+        .execute(&self.pool)
+        .await?;
+
+        self.backfill_aggregate_types().await?;
+
+        Ok(())
+    }
+}
+
+impl PostgresEventStore {
+    // Rows written before aggregate types existed carry ''. Each legacy stream
+    // takes the aggregate type its event types map to, so it is not forked by
+    // the first typed append; migration stops while any stream is left untyped.
+    async fn backfill_aggregate_types(&self) -> Result<(), PostgresError> {
+        let (event_types, aggregate_types): (Vec<_>, Vec<_>) = self
+            .legacy_aggregate_types
+            .iter()
+            .map(|(event_type, aggregate_type)| (event_type.clone(), aggregate_type.clone()))
+            .unzip();
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query(
+            "UPDATE events SET aggregate_type = streams.aggregate_type FROM (
+                SELECT events.aggregate_id, MIN(mapping.aggregate_type) AS aggregate_type
+                FROM events JOIN UNNEST($1::text[], $2::text[]) AS mapping(event_type, aggregate_type)
+                    ON events.event_type = mapping.event_type
+                WHERE events.aggregate_type = ''
+                GROUP BY events.aggregate_id
+                HAVING COUNT(DISTINCT mapping.aggregate_type) = 1
+            ) AS streams
+            WHERE events.aggregate_type = '' AND events.aggregate_id = streams.aggregate_id",
+        )
+        .bind(&event_types)
+        .bind(&aggregate_types)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            "UPDATE outbox SET aggregate_type = mapping.aggregate_type
+            FROM UNNEST($1::text[], $2::text[]) AS mapping(event_type, aggregate_type)
+            WHERE outbox.aggregate_type = '' AND outbox.event_type = mapping.event_type",
+        )
+        .bind(&event_types)
+        .bind(&aggregate_types)
+        .execute(&mut *tx)
+        .await?;
+
+        // Snapshots are only a cache, so untyped ones are rebuilt from events.
+        sqlx::query("DELETE FROM snapshots WHERE aggregate_type = ''")
+            .execute(&mut *tx)
+            .await?;
+
+        let untyped: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM events WHERE aggregate_type = ''")
+            .fetch_one(&mut *tx)
+            .await?;
+        if untyped > 0 {
+            return Err(PostgresError::UntypedEvents(untyped));
+        }
+
+        tx.commit().await?;
         Ok(())
     }
 }
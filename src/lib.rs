@@ -1,36 +1,69 @@
 pub mod aggregate;
+pub mod aggregate_id;
+pub mod bus_error;
+pub mod checkpoint;
+pub mod checkpoint_postgres;
 pub mod command;
 pub mod command_bus;
 pub mod command_handler;
 pub mod event;
 pub mod event_bus;
 pub mod event_bus_rabbit;
+pub mod event_consumer_rabbit;
+pub mod event_filter;
 pub mod event_handler;
 pub mod event_metadata;
 pub mod event_store;
 pub mod event_store_postgres;
+pub mod message_context;
+pub mod middleware;
+pub mod outbox_postgres;
+pub mod projection_rebuild;
 pub mod projections;
 pub mod query;
 pub mod query_bus;
 pub mod query_handler;
+pub mod retry;
 pub mod snapshot;
+pub mod snapshot_postgres;
+pub mod subscription;
+pub mod upcaster;
 
 pub use aggregate::Aggregate;
-pub use command::Command;
+pub use aggregate_id::AggregateId;
+pub use bus_error::BusError;
+pub use checkpoint::{CheckpointStore, InMemoryCheckpointStore};
+pub use checkpoint_postgres::PostgresCheckpointStore;
+pub use command::{Command, CommandOutput, CommandResult, ErasedCommandResult};
 pub use command_bus::{CommandBus, InMemoryCommandBus};
 pub use command_handler::{CommandHandler, CommandHandlerError};
 pub use event::Event;
-pub use event_bus::{EventBus, InMemoryEventBus};
-pub use event_bus_rabbit::RabbitEventBus;
+pub use event_bus::{DeadLetter, DispatchMode, DispatchReport, EventBus, HandlerFailure, InMemoryEventBus};
+pub use event_bus_rabbit::{ConnectionState, RabbitError, RabbitEventBus};
+pub use event_consumer_rabbit::RabbitEventConsumer;
+pub use event_filter::EventFilter;
 pub use event_handler::{EventHandler, ProjectionEventHandler};
 pub use event_metadata::{EventEnvelope, EventMetadata};
-pub use event_store::EventStore;
+pub use event_store::{
+    AggregateTypeMismatch, EventStore, EventStoreError, InMemoryEventStore, InMemoryEventStoreError, StoredEvent,
+};
 pub use event_store_postgres::{Migrator, PostgresEventStore};
-pub use projections::Projection;
+pub use message_context::MessageContext;
+pub use middleware::{Middleware, Next};
+pub use outbox_postgres::OutboxRelay;
+pub use projection_rebuild::{ProjectionRebuild, RebuildError, RebuildProgress};
+pub use projections::{Projection, ResettableProjection, ShadowProjection};
 pub use query::Query;
 pub use query_bus::{InMemoryQueryBus, QueryBus};
 pub use query_handler::QueryHandler;
-pub use snapshot::{Snapshot, SnapshotStore};
+pub use retry::RetryPolicy;
+pub use snapshot::{
+    EventsSinceLastSnapshotPolicy, EveryNEventsPolicy, NeverPolicy, Snapshot, SnapshotContext, SnapshotPolicy,
+    SnapshotStore, TimeBasedPolicy,
+};
+pub use snapshot_postgres::PostgresSnapshotStore;
+pub use subscription::{CatchUpSubscription, SubscriptionError};
+pub use upcaster::{RawEvent, UpcastError, Upcaster, UpcasterRegistry};
 
 #[derive(Debug)]
 pub enum FrameworkError {
@@ -38,11 +71,11 @@ pub enum FrameworkError {
     Migration(Box<dyn std::error::Error + Send + Sync>),
 }
 
-pub struct Framework<E: Event> {
-    pub event_store: Box<dyn EventStore<E, String, Error = FrameworkError> + Send + Sync>,
+pub struct Framework<E: Event, Id: AggregateId = String> {
+    pub event_store: Box<dyn EventStore<E, Id, Error = FrameworkError> + Send + Sync>,
     migrator: Box<dyn Migrator + Send + Sync>,
 }
 
-impl<E: Event> Framework<E> {
+impl<E: Event, Id: AggregateId> Framework<E, Id> {
     pub async fn setup(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> { self.migrator.migrate().await }
 }
@@ -0,0 +1,78 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::future::Future;
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::EventMetadata;
+
+tokio::task_local! {
+    static CURRENT: MessageContext;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MessageContext {
+    pub correlation_id: Uuid,
+    pub causation_id: Option<Uuid>,
+    pub user_id: Option<String>,
+    pub tenant_id: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+}
+
+impl Default for MessageContext {
+    fn default() -> Self { Self::new(Uuid::new_v4()) }
+}
+
+impl MessageContext {
+    pub fn new(correlation_id: Uuid) -> Self {
+        Self {
+            correlation_id,
+            causation_id: None,
+            user_id: None,
+            tenant_id: None,
+            headers: BTreeMap::new(),
+        }
+    }
+
+    // Only headers named in `propagated_headers` carry over to follow-up
+    // messages; everything else stays on the event that declared it.
+    pub fn from_metadata(metadata: &EventMetadata, propagated_headers: &BTreeSet<String>) -> Self {
+        Self {
+            correlation_id: metadata.correlation_id,
+            causation_id: Some(metadata.event_id),
+            user_id: metadata.user_id.clone(),
+            tenant_id: metadata.tenant_id.clone(),
+            headers: metadata
+                .headers
+                .iter()
+                .filter(|(key, _)| propagated_headers.contains(*key))
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect(),
+        }
+    }
+
+    pub fn with_causation_id(mut self, causation_id: Uuid) -> Self {
+        self.causation_id = Some(causation_id);
+        self
+    }
+
+    pub fn with_user_id(mut self, user_id: impl Into<String>) -> Self {
+        self.user_id = Some(user_id.into());
+        self
+    }
+
+    pub fn with_tenant_id(mut self, tenant_id: impl Into<String>) -> Self {
+        self.tenant_id = Some(tenant_id.into());
+        self
+    }
+
+    pub fn with_header(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.insert(key.into(), value.into());
+        self
+    }
+
+    pub fn current() -> Option<MessageContext> { CURRENT.try_with(Clone::clone).ok() }
+
+    pub async fn scope<F: Future>(self, future: F) -> F::Output { CURRENT.scope(self, future).await }
+}
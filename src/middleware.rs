@@ -0,0 +1,61 @@
+use std::any::Any;
+
+use async_trait::async_trait;
+
+use crate::BusError;
+use crate::command_bus::ErasedCommandHandler;
+use crate::query_bus::ErasedQueryHandler;
+
+#[async_trait]
+pub trait Middleware: Send + Sync {
+    async fn handle(
+        &self, message_type: &'static str, message: &(dyn Any + Send + Sync), next: Next<'_>,
+    ) -> Result<Box<dyn Any + Send>, BusError>;
+}
+
+#[async_trait]
+pub(crate) trait Dispatch: Send + Sync {
+    async fn dispatch(&self, message: &(dyn Any + Send + Sync)) -> Result<Box<dyn Any + Send>, BusError>;
+}
+
+#[async_trait]
+impl Dispatch for Box<dyn ErasedCommandHandler> {
+    async fn dispatch(&self, message: &(dyn Any + Send + Sync)) -> Result<Box<dyn Any + Send>, BusError> {
+        self.handle(message).await
+    }
+}
+
+#[async_trait]
+impl Dispatch for Box<dyn ErasedQueryHandler> {
+    async fn dispatch(&self, message: &(dyn Any + Send + Sync)) -> Result<Box<dyn Any + Send>, BusError> {
+        self.handle(message).await
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Next<'a> {
+    middleware: &'a [Box<dyn Middleware>],
+    handler: &'a dyn Dispatch,
+}
+
+impl<'a> Next<'a> {
+    pub(crate) fn new(middleware: &'a [Box<dyn Middleware>], handler: &'a dyn Dispatch) -> Self {
+        Self {
+            middleware,
+            handler,
+        }
+    }
+
+    pub async fn run(
+        self, message_type: &'static str, message: &(dyn Any + Send + Sync),
+    ) -> Result<Box<dyn Any + Send>, BusError> {
+        match self.middleware.split_first() {
+            Some((middleware, rest)) => {
+                middleware
+                    .handle(message_type, message, Next::new(rest, self.handler))
+                    .await
+            },
+            None => self.handler.dispatch(message).await,
+        }
+    }
+}
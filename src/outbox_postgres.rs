@@ -0,0 +1,206 @@
+use std::fmt::Debug;
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::Deserialize;
+use serde_json::Value;
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Row};
+
+use crate::event_store_postgres::{PostgresError, decode_envelopes};
+use crate::{Event, EventBus, EventEnvelope, EventMetadata, RawEvent, RetryPolicy, UpcasterRegistry};
+
+pub struct OutboxRelay<E: Event, B: EventBus<E>> {
+    pool: PgPool,
+    event_bus: B,
+    batch_size: usize,
+    poll_interval: Duration,
+    retry_policy: RetryPolicy,
+    max_attempts: u32,
+    lease: Duration,
+    upcasters: Arc<UpcasterRegistry>,
+    _phantom: PhantomData<fn() -> E>,
+}
+
+impl<E, B> OutboxRelay<E, B>
+where
+    E: Event + for<'de> Deserialize<'de>,
+    B: EventBus<E> + Send + Sync,
+    B::Error: Debug,
+{
+    pub fn new(pool: PgPool, event_bus: B) -> Self {
+        Self {
+            pool,
+            event_bus,
+            batch_size: 100,
+            poll_interval: Duration::from_secs(1),
+            retry_policy: RetryPolicy::new(3),
+            max_attempts: 10,
+            lease: Duration::from_secs(30),
+            upcasters: Arc::new(UpcasterRegistry::new()),
+            _phantom: PhantomData,
+        }
+    }
+
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    pub fn with_lease(mut self, lease: Duration) -> Self {
+        self.lease = lease;
+        self
+    }
+
+    pub fn with_upcasters(mut self, upcasters: Arc<UpcasterRegistry>) -> Self {
+        self.upcasters = upcasters;
+        self
+    }
+
+    pub async fn run(&self) -> Result<(), PostgresError> {
+        log::info!("Starting outbox relay");
+
+        loop {
+            if self.relay_batch().await? == 0 {
+                tokio::time::sleep(self.poll_interval).await;
+            }
+        }
+    }
+
+    pub async fn relay_batch(&self) -> Result<usize, PostgresError> {
+        let mut rows = sqlx::query(
+            "UPDATE outbox SET locked_until = NOW() + make_interval(secs => $2) WHERE id IN (SELECT id FROM outbox \
+             WHERE delivered_at IS NULL AND failed_at IS NULL AND (locked_until IS NULL OR locked_until < NOW()) \
+             ORDER BY id LIMIT $1 FOR UPDATE SKIP LOCKED) RETURNING id, aggregate_type, aggregate_id, event_type, \
+             schema_version, payload",
+        )
+        .bind(self.batch_size as i64)
+        .bind(self.lease.as_secs_f64())
+        .fetch_all(&self.pool)
+        .await?;
+        rows.sort_by_key(|row| row.get::<i64, _>("id"));
+
+        let mut delivered = 0;
+        for (i, row) in rows.iter().enumerate() {
+            let id: i64 = row.get("id");
+            let envelopes = match self.decode(row) {
+                Ok(envelopes) => envelopes,
+                Err(err) => {
+                    log::error!("Failed to decode outbox message {}, marking it failed: {}", id, err);
+
+                    sqlx::query(
+                        "UPDATE outbox SET failed_at = NOW(), locked_until = NULL, last_error = $2 WHERE id = $1",
+                    )
+                    .bind(id)
+                    .bind(err.to_string())
+                    .execute(&self.pool)
+                    .await?;
+
+                    continue;
+                },
+            };
+
+            match self.publish_with_retry(&envelopes).await {
+                Ok(()) => {
+                    sqlx::query(
+                        "UPDATE outbox SET delivered_at = NOW(), attempts = attempts + 1, locked_until = NULL WHERE \
+                         id = $1",
+                    )
+                    .bind(id)
+                    .execute(&self.pool)
+                    .await?;
+
+                    delivered += 1;
+                },
+                Err(err) => {
+                    let failed: bool = sqlx::query_scalar(
+                        "UPDATE outbox SET attempts = attempts + 1, last_error = $2, locked_until = NULL, failed_at = \
+                         CASE WHEN attempts + 1 >= $3 THEN NOW() END WHERE id = $1 RETURNING failed_at IS NOT NULL",
+                    )
+                    .bind(id)
+                    .bind(format!("{:?}", err))
+                    .bind(self.max_attempts as i32)
+                    .fetch_one(&self.pool)
+                    .await?;
+
+                    if failed {
+                        log::error!(
+                            "Giving up on outbox message {} after {} attempts: {:?}",
+                            id,
+                            self.max_attempts,
+                            err
+                        );
+                        continue;
+                    }
+
+                    log::warn!("Failed to relay outbox message {}: {:?}", id, err);
+
+                    let pending: Vec<i64> = rows[i + 1..].iter().map(|row| row.get("id")).collect();
+                    sqlx::query("UPDATE outbox SET locked_until = NULL WHERE id = ANY($1)")
+                        .bind(pending)
+                        .execute(&self.pool)
+                        .await?;
+
+                    break;
+                },
+            }
+        }
+
+        if delivered > 0 {
+            log::info!("Relayed {} outbox messages", delivered);
+        }
+
+        Ok(delivered)
+    }
+
+    fn decode(&self, row: &PgRow) -> Result<Vec<EventEnvelope<E>>, PostgresError> {
+        let mut payload: Value = row.get("payload");
+        let mut metadata: EventMetadata = serde_json::from_value(payload["metadata"].take())?;
+        if metadata.aggregate_type.is_none() {
+            metadata.aggregate_type = Some(row.get("aggregate_type"));
+        }
+        if metadata.aggregate_id.is_none() {
+            metadata.aggregate_id = Some(row.get("aggregate_id"));
+        }
+        let raw = RawEvent {
+            event_type: row.get("event_type"),
+            schema_version: row.get::<i32, _>("schema_version") as u32,
+            data: payload["event"].take(),
+        };
+
+        decode_envelopes(&self.upcasters, raw, metadata)
+    }
+
+    async fn publish_with_retry(&self, envelopes: &[EventEnvelope<E>]) -> Result<(), B::Error> {
+        let mut attempt = 1;
+
+        loop {
+            match self.event_bus.publish(envelopes).await {
+                Ok(()) => return Ok(()),
+                Err(err) if !self.retry_policy.should_retry(attempt) => return Err(err),
+                Err(err) => {
+                    log::debug!("Publish attempt {} failed: {:?}", attempt, err);
+                },
+            }
+
+            tokio::time::sleep(self.retry_policy.backoff(attempt)).await;
+            attempt += 1;
+        }
+    }
+}
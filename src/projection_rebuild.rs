@@ -0,0 +1,147 @@
+use std::collections::HashSet;
+use std::marker::PhantomData;
+
+use crate::{Event, EventStore, Projection, ResettableProjection, ShadowProjection};
+
+#[derive(Debug, Clone, Default)]
+pub struct RebuildProgress {
+    pub position: u64,
+    pub events_read: u64,
+    pub events_applied: u64,
+}
+
+#[derive(Debug)]
+pub enum RebuildError<S, P> {
+    EventStore(S),
+    Projection(P),
+}
+
+type ProgressCallback<'a> = Box<dyn Fn(&RebuildProgress) + Send + Sync + 'a>;
+
+pub struct ProjectionRebuild<'a, S, Id> {
+    event_store: &'a S,
+    event_types: Option<HashSet<String>>,
+    batch_size: usize,
+    on_progress: Option<ProgressCallback<'a>>,
+    _phantom: PhantomData<fn() -> Id>,
+}
+
+impl<'a, S, Id> ProjectionRebuild<'a, S, Id> {
+    pub fn new(event_store: &'a S) -> Self {
+        Self {
+            event_store,
+            event_types: None,
+            batch_size: 500,
+            on_progress: None,
+            _phantom: PhantomData,
+        }
+    }
+
+    pub fn with_event_types<T: Into<String>>(mut self, event_types: impl IntoIterator<Item = T>) -> Self {
+        self.event_types = Some(event_types.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    pub fn on_progress(mut self, on_progress: impl Fn(&RebuildProgress) + Send + Sync + 'a) -> Self {
+        self.on_progress = Some(Box::new(on_progress));
+        self
+    }
+
+    pub async fn rebuild<P>(&self, projection: &P) -> Result<RebuildProgress, RebuildError<S::Error, P::Error>>
+    where
+        P: ResettableProjection,
+        P::Event: Event,
+        S: EventStore<P::Event, Id> + Sync,
+    {
+        log::info!("Resetting projection: {}", std::any::type_name::<P>());
+
+        projection.reset().await.map_err(RebuildError::Projection)?;
+
+        self.replay(projection).await
+    }
+
+    pub async fn rebuild_shadow<P>(&self, projection: &P) -> Result<RebuildProgress, RebuildError<S::Error, P::Error>>
+    where
+        P: ShadowProjection,
+        P::Event: Event,
+        S: EventStore<P::Event, Id> + Sync,
+    {
+        log::info!("Rebuilding shadow of projection: {}", std::any::type_name::<P>());
+
+        let shadow = projection.create_shadow().await.map_err(RebuildError::Projection)?;
+        shadow.reset().await.map_err(RebuildError::Projection)?;
+
+        let progress = self.replay(&shadow).await?;
+
+        log::info!("Swapping in shadow projection at position {}", progress.position);
+
+        projection
+            .swap(shadow, progress.position)
+            .await
+            .map_err(RebuildError::Projection)?;
+
+        Ok(progress)
+    }
+
+    async fn replay<P>(&self, projection: &P) -> Result<RebuildProgress, RebuildError<S::Error, P::Error>>
+    where
+        P: Projection,
+        P::Event: Event,
+        S: EventStore<P::Event, Id> + Sync,
+    {
+        let mut progress = RebuildProgress::default();
+
+        loop {
+            let batch = self
+                .event_store
+                .read_all(progress.position, self.batch_size)
+                .await
+                .map_err(RebuildError::EventStore)?;
+
+            let Some(last) = batch.last() else {
+                break;
+            };
+            progress.position = last.position;
+            let batch_len = batch.len();
+
+            for stored in &batch {
+                progress.events_read += 1;
+
+                let event = &stored.envelope.event;
+                if self
+                    .event_types
+                    .as_ref()
+                    .is_some_and(|event_types| !event_types.contains(event.event_type()))
+                {
+                    continue;
+                }
+
+                projection.apply(event).await.map_err(RebuildError::Projection)?;
+                progress.events_applied += 1;
+            }
+
+            log::debug!(
+                "Rebuild progress: position {}, {} events applied",
+                progress.position,
+                progress.events_applied
+            );
+
+            if let Some(on_progress) = &self.on_progress {
+                on_progress(&progress);
+            }
+
+            if batch_len < self.batch_size {
+                break;
+            }
+        }
+
+        log::info!("Rebuild finished with {} events applied", progress.events_applied);
+
+        Ok(progress)
+    }
+}
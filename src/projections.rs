@@ -7,3 +7,16 @@ pub trait Projection: Send + Sync {
 
     async fn apply(&self, event: &Self::Event) -> Result<(), Self::Error>;
 }
+
+#[async_trait]
+pub trait ResettableProjection: Projection {
+    async fn reset(&self) -> Result<(), Self::Error>;
+}
+
+#[async_trait]
+pub trait ShadowProjection: Projection {
+    type Shadow: ResettableProjection<Event = Self::Event, Error = Self::Error>;
+
+    async fn create_shadow(&self) -> Result<Self::Shadow, Self::Error>;
+    async fn swap(&self, shadow: Self::Shadow, position: u64) -> Result<(), Self::Error>;
+}
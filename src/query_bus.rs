@@ -1,9 +1,10 @@
-use std::any::TypeId;
+use std::any::{Any, TypeId};
 use std::collections::HashMap;
 
 use async_trait::async_trait;
 
-use crate::{Query, QueryHandler};
+use crate::middleware::Next;
+use crate::{BusError, Middleware, Query, QueryHandler};
 
 #[async_trait]
 pub trait QueryBus {
@@ -14,9 +15,7 @@ pub trait QueryBus {
 
 #[async_trait]
 pub trait ErasedQueryHandler: Send + Sync {
-    async fn handle(
-        &self, query: Box<dyn std::any::Any + Send>,
-    ) -> Result<Box<dyn std::any::Any + Send>, Box<dyn std::error::Error + Send + Sync>>;
+    async fn handle(&self, query: &(dyn Any + Send + Sync)) -> Result<Box<dyn Any + Send>, BusError>;
 }
 
 pub struct TypedQueryHandler<Q: Query, H: QueryHandler<Q>> {
@@ -34,22 +33,29 @@ impl<Q: Query, H: QueryHandler<Q>> TypedQueryHandler<Q, H> {
 }
 
 #[async_trait]
-impl<Q: Query + 'static, H: QueryHandler<Q> + Send + Sync> ErasedQueryHandler for TypedQueryHandler<Q, H> {
-    async fn handle(
-        &self, query: Box<dyn std::any::Any + Send>,
-    ) -> Result<Box<dyn std::any::Any + Send>, Box<dyn std::error::Error + Send + Sync>> {
-        let typed_query = *query.downcast::<Q>().map_err(|_| "Type mismatch")?;
+impl<Q: Query + 'static, H: QueryHandler<Q> + Send + Sync> ErasedQueryHandler for TypedQueryHandler<Q, H>
+where
+    H::Error: std::error::Error + Send + Sync + 'static,
+{
+    async fn handle(&self, query: &(dyn Any + Send + Sync)) -> Result<Box<dyn Any + Send>, BusError> {
+        let typed_query = query.downcast_ref::<Q>().ok_or_else(|| {
+            BusError::TypeMismatch {
+                expected: std::any::type_name::<Q>(),
+            }
+        })?;
+
         let result = self
             .handler
-            .handle(typed_query)
+            .handle(typed_query.clone())
             .await
-            .map_err(|_| "Query execution failed")?;
+            .map_err(|err| BusError::Handler(Box::new(err)))?;
         Ok(Box::new(result))
     }
 }
 
 pub struct InMemoryQueryBus {
     handlers: HashMap<TypeId, Box<dyn ErasedQueryHandler>>,
+    middleware: Vec<Box<dyn Middleware>>,
 }
 
 impl Default for InMemoryQueryBus {
@@ -60,13 +66,20 @@ impl InMemoryQueryBus {
     pub fn new() -> Self {
         Self {
             handlers: HashMap::new(),
+            middleware: Vec::new(),
         }
     }
 
+    pub fn with_middleware<M: Middleware + 'static>(mut self, middleware: M) -> Self {
+        self.middleware.push(Box::new(middleware));
+        self
+    }
+
     pub fn register_handler<Q, H>(&mut self, handler: H)
     where
         Q: Query + 'static,
         H: QueryHandler<Q> + Send + Sync + 'static,
+        H::Error: std::error::Error + Send + Sync + 'static,
     {
         log::info!("Registering query handler for: {}", std::any::type_name::<Q>());
 
@@ -77,14 +90,22 @@ impl InMemoryQueryBus {
 
 #[async_trait]
 impl QueryBus for InMemoryQueryBus {
-    type Error = Box<dyn std::error::Error + Send + Sync>;
+    type Error = BusError;
 
     async fn send<Q: Query + 'static>(&self, query: Q) -> Result<Q::Result, Self::Error> {
         let type_id = TypeId::of::<Q>();
-        let handler = self.handlers.get(&type_id).ok_or("No handler registered for query")?;
-
-        let result = handler.handle(Box::new(query)).await?;
-        let typed_result = *result.downcast::<Q::Result>().map_err(|_| "Result type mismatch")?;
+        let handler = self.handlers.get(&type_id).ok_or(BusError::NoHandler {
+            message_type: std::any::type_name::<Q>(),
+        })?;
+
+        let result = Next::new(&self.middleware, handler)
+            .run(std::any::type_name::<Q>(), &query)
+            .await?;
+        let typed_result = *result.downcast::<Q::Result>().map_err(|_| {
+            BusError::TypeMismatch {
+                expected: std::any::type_name::<Q::Result>(),
+            }
+        })?;
         Ok(typed_result)
     }
 }
@@ -1,4 +1,7 @@
+use std::time::Duration;
+
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 
 use crate::Aggregate;
 
@@ -17,3 +20,86 @@ pub trait SnapshotStore<S: Snapshot, Id> {
 impl<A: Aggregate + Clone> Snapshot for A {
     fn version(&self) -> u64 { self.version() }
 }
+
+#[derive(Debug, Clone)]
+pub struct SnapshotContext {
+    pub last_snapshot_version: u64,
+    pub previous_version: u64,
+    pub current_version: u64,
+    pub oldest_unsnapshotted_event_at: Option<DateTime<Utc>>,
+}
+
+pub trait SnapshotPolicy: Send + Sync {
+    fn should_snapshot(&self, context: &SnapshotContext) -> bool;
+}
+
+#[derive(Debug, Clone)]
+pub struct EveryNEventsPolicy {
+    interval: u64,
+}
+
+impl EveryNEventsPolicy {
+    pub fn new(interval: u64) -> Self {
+        Self {
+            interval,
+        }
+    }
+}
+
+impl SnapshotPolicy for EveryNEventsPolicy {
+    fn should_snapshot(&self, context: &SnapshotContext) -> bool {
+        if self.interval == 0 {
+            return false;
+        }
+
+        context.previous_version / self.interval != context.current_version / self.interval
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EventsSinceLastSnapshotPolicy {
+    threshold: u64,
+}
+
+impl EventsSinceLastSnapshotPolicy {
+    pub fn new(threshold: u64) -> Self {
+        Self {
+            threshold,
+        }
+    }
+}
+
+impl SnapshotPolicy for EventsSinceLastSnapshotPolicy {
+    fn should_snapshot(&self, context: &SnapshotContext) -> bool {
+        context.current_version.saturating_sub(context.last_snapshot_version) >= self.threshold.max(1)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TimeBasedPolicy {
+    interval: Duration,
+}
+
+impl TimeBasedPolicy {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+        }
+    }
+}
+
+impl SnapshotPolicy for TimeBasedPolicy {
+    fn should_snapshot(&self, context: &SnapshotContext) -> bool {
+        context
+            .oldest_unsnapshotted_event_at
+            .and_then(|timestamp| Utc::now().signed_duration_since(timestamp).to_std().ok())
+            .is_some_and(|elapsed| elapsed >= self.interval)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct NeverPolicy;
+
+impl SnapshotPolicy for NeverPolicy {
+    fn should_snapshot(&self, _context: &SnapshotContext) -> bool { false }
+}
@@ -0,0 +1,60 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Row};
+
+use crate::event_store_postgres::PostgresError;
+use crate::{Aggregate, AggregateId, SnapshotStore};
+
+#[derive(Clone)]
+pub struct PostgresSnapshotStore {
+    pub(crate) pool: PgPool,
+}
+
+impl PostgresSnapshotStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            pool,
+        }
+    }
+}
+
+#[async_trait]
+impl<S: Aggregate + Serialize + for<'de> Deserialize<'de> + 'static, Id: AggregateId> SnapshotStore<S, Id>
+    for PostgresSnapshotStore
+{
+    type Error = PostgresError;
+
+    async fn save_snapshot(&self, aggregate_id: &Id, snapshot: S) -> Result<(), Self::Error> {
+        let snapshot_data = serde_json::to_value(&snapshot)?;
+
+        sqlx::query(
+            "INSERT INTO snapshots (aggregate_type, aggregate_id, version, snapshot_data) VALUES ($1, $2, $3, $4) ON \
+             CONFLICT (aggregate_type, aggregate_id, version) DO UPDATE SET snapshot_data = EXCLUDED.snapshot_data, \
+             created_at = NOW()",
+        )
+        .bind(S::aggregate_type())
+        .bind(aggregate_id.encode())
+        .bind(Aggregate::version(&snapshot) as i64)
+        .bind(snapshot_data)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_snapshot(&self, aggregate_id: &Id) -> Result<Option<S>, Self::Error> {
+        let row = sqlx::query(
+            "SELECT snapshot_data FROM snapshots WHERE aggregate_type = $1 AND aggregate_id = $2 ORDER BY version \
+             DESC LIMIT 1",
+        )
+        .bind(S::aggregate_type())
+        .bind(aggregate_id.encode())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        match row {
+            Some(row) => Ok(Some(serde_json::from_value(row.get("snapshot_data"))?)),
+            None => Ok(None),
+        }
+    }
+}
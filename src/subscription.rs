@@ -0,0 +1,123 @@
+use std::marker::PhantomData;
+use std::time::Duration;
+
+use crate::{CheckpointStore, Event, EventStore, Projection};
+
+#[derive(Debug)]
+pub enum SubscriptionError<S, P, C> {
+    EventStore(S),
+    Projection(P),
+    Checkpoint(C),
+}
+
+pub struct CatchUpSubscription<S, P, C, Id> {
+    name: String,
+    event_store: S,
+    projection: P,
+    checkpoint_store: C,
+    batch_size: usize,
+    poll_interval: Duration,
+    _phantom: PhantomData<fn() -> Id>,
+}
+
+type CatchUpError<S, P, C, Id> = SubscriptionError<
+    <S as EventStore<<P as Projection>::Event, Id>>::Error,
+    <P as Projection>::Error,
+    <C as CheckpointStore>::Error,
+>;
+
+impl<S, P, C, Id> CatchUpSubscription<S, P, C, Id>
+where
+    P: Projection,
+    P::Event: Event,
+    S: EventStore<P::Event, Id> + Send + Sync,
+    C: CheckpointStore + Send + Sync,
+{
+    pub fn new(name: impl Into<String>, event_store: S, projection: P, checkpoint_store: C) -> Self {
+        Self {
+            name: name.into(),
+            event_store,
+            projection,
+            checkpoint_store,
+            batch_size: 500,
+            poll_interval: Duration::from_millis(500),
+            _phantom: PhantomData,
+        }
+    }
+
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    pub fn name(&self) -> &str { &self.name }
+
+    pub fn projection(&self) -> &P { &self.projection }
+
+    pub async fn catch_up(&self) -> Result<u64, CatchUpError<S, P, C, Id>> {
+        let position = self.load_position().await?;
+        self.process_from(position).await
+    }
+
+    pub async fn run(&self) -> Result<(), CatchUpError<S, P, C, Id>> {
+        let mut position = self.load_position().await?;
+
+        log::info!("Starting subscription {} from position {}", self.name, position);
+
+        loop {
+            position = self.process_from(position).await?;
+            tokio::time::sleep(self.poll_interval).await;
+        }
+    }
+
+    async fn load_position(&self) -> Result<u64, CatchUpError<S, P, C, Id>> {
+        let position = self
+            .checkpoint_store
+            .load_checkpoint(&self.name)
+            .await
+            .map_err(SubscriptionError::Checkpoint)?;
+
+        Ok(position.unwrap_or(0))
+    }
+
+    async fn process_from(&self, mut position: u64) -> Result<u64, CatchUpError<S, P, C, Id>> {
+        loop {
+            let batch = self
+                .event_store
+                .read_all(position, self.batch_size)
+                .await
+                .map_err(SubscriptionError::EventStore)?;
+
+            let Some(last) = batch.last() else {
+                return Ok(position);
+            };
+            let last_position = last.position;
+            let batch_len = batch.len();
+
+            log::debug!("Subscription {} applying {} events", self.name, batch_len);
+
+            for stored in &batch {
+                self.projection
+                    .apply(&stored.envelope.event)
+                    .await
+                    .map_err(SubscriptionError::Projection)?;
+            }
+
+            self.checkpoint_store
+                .save_checkpoint(&self.name, last_position)
+                .await
+                .map_err(SubscriptionError::Checkpoint)?;
+
+            position = last_position;
+
+            if batch_len < self.batch_size {
+                return Ok(position);
+            }
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RawEvent {
+    pub event_type: String,
+    pub schema_version: u32,
+    pub data: Value,
+}
+
+pub trait Upcaster: Send + Sync {
+    fn event_type(&self) -> &str;
+    fn schema_version(&self) -> u32;
+
+    fn upcast(&self, event: RawEvent) -> Vec<RawEvent>;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum UpcastError {
+    NotUpgraded { event_type: String, schema_version: u32 },
+    TooDeep { event_type: String, schema_version: u32 },
+}
+
+impl std::fmt::Display for UpcastError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UpcastError::NotUpgraded {
+                event_type,
+                schema_version,
+            } => {
+                write!(
+                    f,
+                    "Upcaster for {} v{} did not raise the schema version",
+                    event_type, schema_version
+                )
+            },
+            UpcastError::TooDeep {
+                event_type,
+                schema_version,
+            } => {
+                write!(
+                    f,
+                    "Upcasting {} v{} exceeded {} steps",
+                    event_type, schema_version, MAX_UPCAST_DEPTH
+                )
+            },
+        }
+    }
+}
+
+impl std::error::Error for UpcastError {}
+
+const MAX_UPCAST_DEPTH: usize = 32;
+
+#[derive(Default)]
+pub struct UpcasterRegistry {
+    upcasters: HashMap<(String, u32), Box<dyn Upcaster>>,
+}
+
+impl UpcasterRegistry {
+    pub fn new() -> Self { Self::default() }
+
+    pub fn with_upcaster<U: Upcaster + 'static>(mut self, upcaster: U) -> Self {
+        log::info!(
+            "Registering upcaster for {} v{}",
+            upcaster.event_type(),
+            upcaster.schema_version()
+        );
+
+        self.upcasters.insert(
+            (upcaster.event_type().to_string(), upcaster.schema_version()),
+            Box::new(upcaster),
+        );
+        self
+    }
+
+    pub fn is_empty(&self) -> bool { self.upcasters.is_empty() }
+
+    pub fn upcast(&self, event: RawEvent) -> Result<Vec<RawEvent>, UpcastError> { self.upcast_from(event, 0) }
+
+    fn upcast_from(&self, event: RawEvent, depth: usize) -> Result<Vec<RawEvent>, UpcastError> {
+        let Some(upcaster) = self.upcasters.get(&(event.event_type.clone(), event.schema_version)) else {
+            return Ok(vec![event]);
+        };
+
+        if depth >= MAX_UPCAST_DEPTH {
+            return Err(UpcastError::TooDeep {
+                event_type: event.event_type,
+                schema_version: event.schema_version,
+            });
+        }
+
+        log::debug!("Upcasting {} from v{}", event.event_type, event.schema_version);
+
+        let (event_type, schema_version) = (event.event_type.clone(), event.schema_version);
+        let mut upcasted = Vec::new();
+        for next in upcaster.upcast(event) {
+            if next.event_type == event_type && next.schema_version <= schema_version {
+                return Err(UpcastError::NotUpgraded {
+                    event_type,
+                    schema_version,
+                });
+            }
+
+            upcasted.extend(self.upcast_from(next, depth + 1)?);
+        }
+
+        Ok(upcasted)
+    }
+}
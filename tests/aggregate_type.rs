@@ -0,0 +1,157 @@
+mod common;
+
+use common::{Counter, CounterEvent, CounterHandler, TestError, add};
+use cqrs_framework::event_store_postgres::PostgresError;
+use cqrs_framework::{
+    Aggregate, AggregateTypeMismatch, CommandHandler, EventEnvelope, EventMetadata, EventStore, Migrator,
+    PostgresEventStore,
+};
+use sqlx::PgPool;
+use sqlx::postgres::PgPoolOptions;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Default)]
+struct Order {
+    version: u64,
+}
+
+impl Aggregate for Order {
+    type Event = CounterEvent;
+
+    fn apply(&mut self, _event: Self::Event) {}
+
+    fn version(&self) -> u64 { self.version }
+
+    fn increment_version(&mut self) { self.version += 1; }
+
+    fn aggregate_type() -> &'static str { "Order" }
+}
+
+// Every test migrates its own schema, so legacy rows seeded by one test never
+// reach another's migration.
+async fn isolated_pool() -> PgPool {
+    let url = std::env::var("DATABASE_URL").expect("DATABASE_URL must point at a Postgres database");
+    let schema = format!("aggregate_type_{}", Uuid::new_v4().simple());
+
+    let admin = PgPool::connect(&url).await.unwrap();
+    sqlx::query(&format!("CREATE SCHEMA {}", schema))
+        .execute(&admin)
+        .await
+        .unwrap();
+
+    PgPoolOptions::new()
+        .after_connect(move |conn, _| {
+            let search_path = format!("SET search_path TO {}", schema);
+            Box::pin(async move {
+                sqlx::query(&search_path).execute(conn).await?;
+                Ok(())
+            })
+        })
+        .connect(&url)
+        .await
+        .unwrap()
+}
+
+async fn migrated_store() -> PostgresEventStore {
+    let store = PostgresEventStore::new(isolated_pool().await);
+    store.migrate().await.unwrap();
+    store
+}
+
+fn added(amount: u64) -> Vec<EventEnvelope<CounterEvent>> {
+    vec![EventEnvelope {
+        event: CounterEvent::Added(amount),
+        metadata: EventMetadata::new(Uuid::new_v4(), None),
+    }]
+}
+
+#[tokio::test]
+#[ignore = "requires DATABASE_URL"]
+async fn handler_rejects_a_store_scoped_to_another_aggregate_type() {
+    let store = migrated_store().await;
+    let handler = CounterHandler::new(store.clone().for_aggregate::<Order>());
+
+    let err = handler.handle(add(&[1])).await.unwrap_err();
+
+    let TestError::EventStore(err) = err else {
+        panic!("expected an event store error, got {:?}", err);
+    };
+    assert_eq!(
+        err.downcast_ref::<AggregateTypeMismatch>(),
+        Some(&AggregateTypeMismatch {
+            expected: "Counter",
+            found: "Order".to_string(),
+        })
+    );
+    let stored = EventStore::<CounterEvent, String>::read_all(&store, 0, 10)
+        .await
+        .unwrap();
+    assert!(stored.is_empty());
+}
+
+#[tokio::test]
+#[ignore = "requires DATABASE_URL"]
+async fn aggregate_types_namespace_streams_with_the_same_id() {
+    let store = migrated_store().await;
+    let counters = store.clone().for_aggregate::<Counter>();
+    let orders = store.clone().for_aggregate::<Order>();
+    let id = "shared-1".to_string();
+
+    counters.save_events(&id, added(1), 0).await.unwrap();
+    orders.save_events(&id, added(2), 0).await.unwrap();
+
+    let counter_events = EventStore::<CounterEvent, String>::get_events(&counters, &id)
+        .await
+        .unwrap();
+    let order_events = EventStore::<CounterEvent, String>::get_events(&orders, &id)
+        .await
+        .unwrap();
+    assert_eq!(counter_events[0].event, CounterEvent::Added(1));
+    assert_eq!(order_events[0].event, CounterEvent::Added(2));
+
+    let scoped = EventStore::<CounterEvent, String>::read_all(&orders, 0, 10)
+        .await
+        .unwrap();
+    assert_eq!(scoped.len(), 1);
+    assert_eq!(scoped[0].envelope.metadata.aggregate_type.as_deref(), Some("Order"));
+
+    let everything = EventStore::<CounterEvent, String>::read_all(&store, 0, 10)
+        .await
+        .unwrap();
+    assert_eq!(everything.len(), 2);
+
+    let err = store.save_events(&id, added(3), 1).await.unwrap_err();
+    assert!(matches!(err, PostgresError::MissingAggregateType));
+}
+
+#[tokio::test]
+#[ignore = "requires DATABASE_URL"]
+async fn migration_types_legacy_streams_before_they_can_fork() {
+    let pool = isolated_pool().await;
+    let store = PostgresEventStore::new(pool.clone());
+    store.migrate().await.unwrap();
+    sqlx::query(
+        "INSERT INTO events (aggregate_type, aggregate_id, event_type, event_data, metadata, version) VALUES ('', $1, \
+         'Added', $2, $3, 1)",
+    )
+    .bind("counter-1")
+    .bind(serde_json::to_value(CounterEvent::Added(5)).unwrap())
+    .bind(serde_json::to_value(EventMetadata::new(Uuid::new_v4(), None)).unwrap())
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let err = store.migrate().await.unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<PostgresError>(),
+        Some(PostgresError::UntypedEvents(1))
+    ));
+
+    let store = store.with_legacy_aggregate_type("Added", "Counter");
+    store.migrate().await.unwrap();
+
+    let handler = CounterHandler::new(store.for_aggregate::<Counter>());
+    let result = handler.handle_with_output(add(&[2])).await.unwrap();
+    assert_eq!(result.version, 2);
+    assert_eq!(result.output, 7);
+}
@@ -0,0 +1,175 @@
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use cqrs_framework::{
+    CatchUpSubscription, CheckpointStore, Event, EventEnvelope, EventMetadata, EventStore, InMemoryCheckpointStore,
+    InMemoryEventStore, Projection, StoredEvent, SubscriptionError,
+};
+use uuid::Uuid;
+
+#[derive(Debug, Clone)]
+struct Numbered(u32);
+
+impl Event for Numbered {
+    fn event_type(&self) -> &'static str { "Numbered" }
+}
+
+struct Shared<T>(Arc<T>);
+
+#[async_trait]
+impl EventStore<Numbered, String> for Shared<InMemoryEventStore<Numbered, String>> {
+    type Error = <InMemoryEventStore<Numbered, String> as EventStore<Numbered, String>>::Error;
+
+    async fn save_events(
+        &self, aggregate_id: &String, events: Vec<EventEnvelope<Numbered>>, expected_version: u64,
+    ) -> Result<(), Self::Error> {
+        self.0.save_events(aggregate_id, events, expected_version).await
+    }
+
+    async fn get_events(&self, aggregate_id: &String) -> Result<Vec<EventEnvelope<Numbered>>, Self::Error> {
+        self.0.get_events(aggregate_id).await
+    }
+
+    async fn get_events_from_version(
+        &self, aggregate_id: &String, from_version: u64,
+    ) -> Result<Vec<EventEnvelope<Numbered>>, Self::Error> {
+        self.0.get_events_from_version(aggregate_id, from_version).await
+    }
+
+    async fn read_all(
+        &self, from_position: u64, batch_size: usize,
+    ) -> Result<Vec<StoredEvent<Numbered, String>>, Self::Error> {
+        self.0.read_all(from_position, batch_size).await
+    }
+}
+
+#[async_trait]
+impl CheckpointStore for Shared<InMemoryCheckpointStore> {
+    type Error = <InMemoryCheckpointStore as CheckpointStore>::Error;
+
+    async fn load_checkpoint(&self, subscription: &str) -> Result<Option<u64>, Self::Error> {
+        self.0.load_checkpoint(subscription).await
+    }
+
+    async fn save_checkpoint(&self, subscription: &str, position: u64) -> Result<(), Self::Error> {
+        self.0.save_checkpoint(subscription, position).await
+    }
+}
+
+#[derive(Default)]
+struct Recorder {
+    seen: Mutex<Vec<u32>>,
+    fail_on: Option<u32>,
+}
+
+#[async_trait]
+impl Projection for Recorder {
+    type Error = u32;
+    type Event = Numbered;
+
+    async fn apply(&self, event: &Self::Event) -> Result<(), Self::Error> {
+        if self.fail_on == Some(event.0) {
+            return Err(event.0);
+        }
+        self.seen.lock().unwrap().push(event.0);
+        Ok(())
+    }
+}
+
+struct Fixture {
+    store: Arc<InMemoryEventStore<Numbered, String>>,
+    checkpoints: Arc<InMemoryCheckpointStore>,
+}
+
+impl Fixture {
+    fn new() -> Self {
+        Self {
+            store: Arc::new(InMemoryEventStore::new()),
+            checkpoints: Arc::new(InMemoryCheckpointStore::new()),
+        }
+    }
+
+    async fn append(&self, aggregate_id: &str, numbers: impl IntoIterator<Item = u32>) {
+        let aggregate_id = aggregate_id.to_string();
+        let version = self.store.get_events(&aggregate_id).await.unwrap().len() as u64;
+        let events = numbers
+            .into_iter()
+            .map(|number| {
+                EventEnvelope {
+                    event: Numbered(number),
+                    metadata: EventMetadata::new(Uuid::new_v4(), None),
+                }
+            })
+            .collect();
+
+        self.store.save_events(&aggregate_id, events, version).await.unwrap();
+    }
+
+    fn subscription(
+        &self, projection: Recorder,
+    ) -> CatchUpSubscription<
+        Shared<InMemoryEventStore<Numbered, String>>,
+        Recorder,
+        Shared<InMemoryCheckpointStore>,
+        String,
+    > {
+        CatchUpSubscription::new(
+            "recorder",
+            Shared(self.store.clone()),
+            projection,
+            Shared(self.checkpoints.clone()),
+        )
+        .with_batch_size(2)
+    }
+}
+
+#[tokio::test]
+async fn catch_up_applies_every_event_in_position_order() {
+    let fixture = Fixture::new();
+    fixture.append("a", [1, 2]).await;
+    fixture.append("b", [3]).await;
+    fixture.append("a", [4, 5]).await;
+
+    let subscription = fixture.subscription(Recorder::default());
+    let position = subscription.catch_up().await.unwrap();
+
+    assert_eq!(position, 5);
+    assert_eq!(*subscription.projection().seen.lock().unwrap(), vec![1, 2, 3, 4, 5]);
+    assert_eq!(fixture.checkpoints.load_checkpoint("recorder").await.unwrap(), Some(5));
+}
+
+#[tokio::test]
+async fn restarted_subscription_resumes_exactly_from_its_checkpoint() {
+    let fixture = Fixture::new();
+    fixture.append("a", [1, 2, 3]).await;
+
+    fixture.subscription(Recorder::default()).catch_up().await.unwrap();
+
+    fixture.append("b", [4, 5]).await;
+
+    let restarted = fixture.subscription(Recorder::default());
+    let position = restarted.catch_up().await.unwrap();
+
+    assert_eq!(position, 5);
+    assert_eq!(*restarted.projection().seen.lock().unwrap(), vec![4, 5]);
+}
+
+#[tokio::test]
+async fn failed_batch_is_not_checkpointed_and_is_retried_on_resume() {
+    let fixture = Fixture::new();
+    fixture.append("a", [1, 2, 3, 4]).await;
+
+    let failing = fixture.subscription(Recorder {
+        fail_on: Some(4),
+        ..Recorder::default()
+    });
+    let err = failing.catch_up().await.unwrap_err();
+
+    assert!(matches!(err, SubscriptionError::Projection(4)));
+    assert_eq!(fixture.checkpoints.load_checkpoint("recorder").await.unwrap(), Some(2));
+
+    let resumed = fixture.subscription(Recorder::default());
+    resumed.catch_up().await.unwrap();
+
+    assert_eq!(*resumed.projection().seen.lock().unwrap(), vec![3, 4]);
+}
@@ -0,0 +1,42 @@
+mod common;
+
+use common::{Add, CounterHandler, add};
+use cqrs_framework::{BusError, CommandBus, CommandHandler, InMemoryCommandBus};
+
+#[tokio::test]
+async fn send_with_output_returns_the_command_output() {
+    let mut bus = InMemoryCommandBus::new();
+    bus.register_handler_with_output::<Add, _>(CounterHandler::default());
+
+    bus.send(add(&[2])).await.unwrap();
+    let result = bus.send_with_output(add(&[3])).await.unwrap();
+
+    assert_eq!(result.output, 5);
+    assert_eq!(result.version, 2);
+    assert_eq!(result.events.len(), 1);
+}
+
+#[tokio::test]
+async fn send_with_output_requires_a_handler_registered_with_output() {
+    let mut bus = InMemoryCommandBus::new();
+    bus.register_handler::<Add, _>(CounterHandler::default());
+
+    let result = bus.send(add(&[2])).await.unwrap();
+    assert_eq!(result.version, 1);
+
+    let err = bus.send_with_output(add(&[3])).await.unwrap_err();
+    assert!(matches!(err, BusError::TypeMismatch { .. }));
+
+    let result = bus.send(add(&[3])).await.unwrap();
+    assert_eq!(result.version, 2);
+}
+
+#[tokio::test]
+async fn handle_with_output_bypasses_the_bus() {
+    let handler: CounterHandler = CounterHandler::default();
+
+    handler.handle(add(&[4])).await.unwrap();
+    let result = handler.handle_with_output(add(&[6])).await.unwrap();
+
+    assert_eq!(result.output, 10);
+}
@@ -0,0 +1,114 @@
+mod common;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+
+use async_trait::async_trait;
+use common::{Add, CounterEvent, CounterHandler, TestError, add};
+use cqrs_framework::{
+    CommandBus, CommandHandler, EventEnvelope, EventMetadata, EventStore, InMemoryCommandBus, InMemoryEventStore,
+    InMemoryEventStoreError, RetryPolicy, StoredEvent,
+};
+use uuid::Uuid;
+
+#[derive(Default)]
+struct ConflictingEventStore {
+    inner: InMemoryEventStore<CounterEvent, String>,
+    conflicts: AtomicU32,
+}
+
+#[async_trait]
+impl EventStore<CounterEvent, String> for ConflictingEventStore {
+    type Error = InMemoryEventStoreError;
+
+    async fn save_events(
+        &self, aggregate_id: &String, events: Vec<EventEnvelope<CounterEvent>>, expected_version: u64,
+    ) -> Result<(), Self::Error> {
+        let conflict = self
+            .conflicts
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |conflicts| conflicts.checked_sub(1))
+            .is_ok();
+
+        if conflict {
+            let competing = EventEnvelope {
+                event: CounterEvent::Added(100),
+                metadata: EventMetadata::new(Uuid::new_v4(), None),
+            };
+            self.inner
+                .save_events(aggregate_id, vec![competing], expected_version)
+                .await?;
+        }
+
+        self.inner.save_events(aggregate_id, events, expected_version).await
+    }
+
+    async fn get_events(&self, aggregate_id: &String) -> Result<Vec<EventEnvelope<CounterEvent>>, Self::Error> {
+        self.inner.get_events(aggregate_id).await
+    }
+
+    async fn get_events_from_version(
+        &self, aggregate_id: &String, from_version: u64,
+    ) -> Result<Vec<EventEnvelope<CounterEvent>>, Self::Error> {
+        self.inner.get_events_from_version(aggregate_id, from_version).await
+    }
+
+    async fn read_all(
+        &self, from_position: u64, batch_size: usize,
+    ) -> Result<Vec<StoredEvent<CounterEvent, String>>, Self::Error> {
+        self.inner.read_all(from_position, batch_size).await
+    }
+}
+
+fn conflicting(conflicts: u32, max_attempts: u32) -> CounterHandler<ConflictingEventStore> {
+    CounterHandler::new(ConflictingEventStore {
+        inner: InMemoryEventStore::new(),
+        conflicts: AtomicU32::new(conflicts),
+    })
+    .with_retry_policy(RetryPolicy::new(max_attempts).with_backoff(Default::default(), Default::default()))
+}
+
+#[tokio::test]
+async fn conflicting_command_is_re_executed_and_appended_once() {
+    let handler = conflicting(1, 3);
+    let executions = Arc::new(AtomicUsize::new(0));
+
+    handler.handle(add(&[5]).counting(&executions)).await.unwrap();
+
+    assert_eq!(executions.load(Ordering::SeqCst), 2);
+
+    let events: Vec<_> = handler
+        .event_store
+        .get_events(&"counter-1".to_string())
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|envelope| envelope.event)
+        .collect();
+    assert_eq!(events, vec![CounterEvent::Added(100), CounterEvent::Added(5)]);
+}
+
+#[tokio::test]
+async fn handler_gives_up_after_max_attempts() {
+    let handler = conflicting(u32::MAX, 3);
+    let executions = Arc::new(AtomicUsize::new(0));
+
+    let err = handler.handle(add(&[5]).counting(&executions)).await.unwrap_err();
+
+    assert!(matches!(err, TestError::Conflict(_)));
+    assert_eq!(executions.load(Ordering::SeqCst), 3);
+
+    let events = handler.event_store.get_events(&"counter-1".to_string()).await.unwrap();
+    assert_eq!(events.len(), 3);
+    assert!(events.iter().all(|envelope| envelope.event == CounterEvent::Added(100)));
+}
+
+#[tokio::test]
+async fn command_bus_surfaces_exhausted_retries_as_a_conflict() {
+    let mut bus = InMemoryCommandBus::new();
+    bus.register_handler::<Add, _>(conflicting(u32::MAX, 2));
+
+    let err = bus.send(add(&[5])).await.unwrap_err();
+
+    assert!(err.is_concurrency_conflict());
+    assert!(matches!(err.downcast_ref::<TestError>(), Some(TestError::Conflict(_))));
+}
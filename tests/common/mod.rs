@@ -0,0 +1,195 @@
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use cqrs_framework::{
+    Aggregate, Command, CommandHandler, CommandHandlerError, CommandOutput, Event, EventStore, EventStoreError,
+    InMemoryEventBus, InMemoryEventStore, RetryPolicy, SnapshotStore,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum CounterEvent {
+    Added(u64),
+}
+
+impl Event for CounterEvent {
+    fn event_type(&self) -> &'static str { "Added" }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Counter {
+    pub total: u64,
+    pub version: u64,
+}
+
+impl Aggregate for Counter {
+    type Event = CounterEvent;
+
+    fn apply(&mut self, event: Self::Event) {
+        match event {
+            CounterEvent::Added(amount) => self.total += amount,
+        }
+    }
+
+    fn version(&self) -> u64 { self.version }
+
+    fn increment_version(&mut self) { self.version += 1; }
+
+    fn aggregate_type() -> &'static str { "Counter" }
+}
+
+pub struct Add {
+    pub id: String,
+    pub amounts: Vec<u64>,
+    pub executions: Arc<AtomicUsize>,
+}
+
+impl Add {
+    pub fn counting(mut self, executions: &Arc<AtomicUsize>) -> Self {
+        self.executions = executions.clone();
+        self
+    }
+}
+
+impl Command for Add {
+    type Aggregate = Counter;
+    type AggregateId = String;
+    type Error = Infallible;
+
+    fn aggregate_id(&self) -> &Self::AggregateId { &self.id }
+
+    fn execute(&self, _aggregate: &Self::Aggregate) -> Result<Vec<CounterEvent>, Self::Error> {
+        self.executions.fetch_add(1, Ordering::SeqCst);
+        Ok(self.amounts.iter().copied().map(CounterEvent::Added).collect())
+    }
+}
+
+impl CommandOutput for Add {
+    type Output = u64;
+
+    fn output(&self, aggregate: &Self::Aggregate) -> Self::Output { aggregate.total }
+}
+
+pub fn add(amounts: &[u64]) -> Add {
+    Add {
+        id: "counter-1".to_string(),
+        amounts: amounts.to_vec(),
+        executions: Arc::default(),
+    }
+}
+
+pub struct InMemorySnapshots<A = Counter> {
+    pub snapshots: Mutex<HashMap<String, A>>,
+}
+
+impl<A> Default for InMemorySnapshots<A> {
+    fn default() -> Self {
+        Self {
+            snapshots: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+#[async_trait]
+impl<A: Aggregate + 'static> SnapshotStore<A, String> for InMemorySnapshots<A> {
+    type Error = ();
+
+    async fn save_snapshot(&self, aggregate_id: &String, snapshot: A) -> Result<(), Self::Error> {
+        self.snapshots.lock().unwrap().insert(aggregate_id.clone(), snapshot);
+        Ok(())
+    }
+
+    async fn get_snapshot(&self, aggregate_id: &String) -> Result<Option<A>, Self::Error> {
+        Ok(self.snapshots.lock().unwrap().get(aggregate_id).cloned())
+    }
+}
+
+#[derive(Debug)]
+pub enum TestError {
+    EventStore(Box<dyn std::error::Error + Send + Sync>),
+    Command(Box<dyn std::error::Error + Send + Sync>),
+    Conflict(Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl std::fmt::Display for TestError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TestError::EventStore(err) => write!(f, "event store error: {}", err),
+            TestError::Command(err) => write!(f, "command error: {}", err),
+            TestError::Conflict(err) => write!(f, "concurrency conflict: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for TestError {}
+
+impl CommandHandlerError for TestError {
+    fn from_event_store_error<E: std::error::Error + Send + Sync + 'static>(err: E) -> Self {
+        TestError::EventStore(Box::new(err))
+    }
+
+    fn from_command_error<E: std::error::Error + Send + Sync + 'static>(err: E) -> Self {
+        TestError::Command(Box::new(err))
+    }
+
+    fn from_concurrency_conflict<E: std::error::Error + Send + Sync + 'static>(err: E) -> Self {
+        TestError::Conflict(Box::new(err))
+    }
+
+    fn is_concurrency_conflict(&self) -> bool { matches!(self, TestError::Conflict(_)) }
+}
+
+pub struct CounterHandler<S = InMemoryEventStore<CounterEvent, String>> {
+    pub event_store: S,
+    pub snapshot_store: InMemorySnapshots,
+    pub event_bus: InMemoryEventBus<CounterEvent>,
+    pub retry_policy: RetryPolicy,
+}
+
+impl Default for CounterHandler {
+    fn default() -> Self { Self::new(InMemoryEventStore::new()) }
+}
+
+impl<S> CounterHandler<S> {
+    pub fn new(event_store: S) -> Self {
+        Self {
+            event_store,
+            snapshot_store: InMemorySnapshots::default(),
+            event_bus: InMemoryEventBus::new(),
+            retry_policy: RetryPolicy::none(),
+        }
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    pub fn with_event_bus(mut self, event_bus: InMemoryEventBus<CounterEvent>) -> Self {
+        self.event_bus = event_bus;
+        self
+    }
+}
+
+impl<S> CommandHandler<Add> for CounterHandler<S>
+where
+    S: EventStore<CounterEvent, String, Error: EventStoreError> + Send + Sync,
+{
+    type Error = TestError;
+    type EventBus = InMemoryEventBus<CounterEvent>;
+    type EventStore = S;
+    type SnapshotStore = InMemorySnapshots;
+
+    fn event_store(&self) -> &Self::EventStore { &self.event_store }
+
+    fn snapshot_store(&self) -> &Self::SnapshotStore { &self.snapshot_store }
+
+    fn event_bus(&self) -> &Self::EventBus { &self.event_bus }
+
+    fn retry_policy(&self) -> RetryPolicy { self.retry_policy.clone() }
+}
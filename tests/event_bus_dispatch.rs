@@ -0,0 +1,152 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use cqrs_framework::{DispatchMode, Event, EventBus, EventEnvelope, EventHandler, EventMetadata, InMemoryEventBus};
+use uuid::Uuid;
+
+#[derive(Debug, Clone)]
+struct Numbered(u32);
+
+impl Event for Numbered {
+    fn event_type(&self) -> &'static str { "Numbered" }
+}
+
+#[derive(Debug)]
+struct Rejected(u32);
+
+impl std::fmt::Display for Rejected {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result { write!(f, "rejected {}", self.0) }
+}
+
+impl std::error::Error for Rejected {}
+
+struct Recorder {
+    seen: Arc<Mutex<Vec<u32>>>,
+    delay: Duration,
+    fail_on: Option<u32>,
+}
+
+impl Recorder {
+    fn new(delay_ms: u64) -> (Self, Arc<Mutex<Vec<u32>>>) {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let recorder = Self {
+            seen: seen.clone(),
+            delay: Duration::from_millis(delay_ms),
+            fail_on: None,
+        };
+        (recorder, seen)
+    }
+
+    fn failing_on(mut self, number: u32) -> Self {
+        self.fail_on = Some(number);
+        self
+    }
+}
+
+#[async_trait]
+impl EventHandler<Numbered> for Recorder {
+    type Error = Rejected;
+
+    async fn handle(&self, envelope: &EventEnvelope<Numbered>) -> Result<(), Self::Error> {
+        // Later events sleep less, so any reordering inside a handler would show up.
+        let number = envelope.event.0;
+        tokio::time::sleep(self.delay / number).await;
+
+        if self.fail_on == Some(number) {
+            return Err(Rejected(number));
+        }
+        self.seen.lock().unwrap().push(number);
+        Ok(())
+    }
+}
+
+struct Tagged {
+    offset: u32,
+    order: Arc<Mutex<Vec<u32>>>,
+}
+
+#[async_trait]
+impl EventHandler<Numbered> for Tagged {
+    type Error = Rejected;
+
+    async fn handle(&self, envelope: &EventEnvelope<Numbered>) -> Result<(), Self::Error> {
+        self.order.lock().unwrap().push(self.offset + envelope.event.0);
+        Ok(())
+    }
+}
+
+fn envelopes(numbers: impl IntoIterator<Item = u32>) -> Vec<EventEnvelope<Numbered>> {
+    numbers
+        .into_iter()
+        .map(|number| {
+            EventEnvelope {
+                event: Numbered(number),
+                metadata: EventMetadata::new(Uuid::new_v4(), None),
+            }
+        })
+        .collect()
+}
+
+fn concurrent_bus() -> InMemoryEventBus<Numbered> {
+    InMemoryEventBus::new().with_dispatch_mode(DispatchMode::Concurrent {
+        max_concurrency: 4,
+    })
+}
+
+#[tokio::test]
+async fn concurrent_dispatch_preserves_order_per_handler() {
+    let mut bus = concurrent_bus();
+    let (slow, slow_seen) = Recorder::new(40);
+    let (fast, fast_seen) = Recorder::new(5);
+    bus.subscribe(slow);
+    bus.subscribe(fast);
+
+    bus.publish(&envelopes(1..=5)).await.unwrap();
+
+    assert_eq!(*slow_seen.lock().unwrap(), vec![1, 2, 3, 4, 5]);
+    assert_eq!(*fast_seen.lock().unwrap(), vec![1, 2, 3, 4, 5]);
+}
+
+#[tokio::test]
+async fn concurrent_dispatch_reports_failures_from_every_handler() {
+    let mut bus = concurrent_bus();
+    let (first, first_seen) = Recorder::new(10);
+    let (second, second_seen) = Recorder::new(10);
+    let (healthy, healthy_seen) = Recorder::new(10);
+    bus.subscribe(first.failing_on(2));
+    bus.subscribe(second.failing_on(3));
+    bus.subscribe(healthy);
+
+    let report = bus.publish(&envelopes(1..=4)).await.unwrap_err();
+
+    let mut failed: Vec<String> = report
+        .failures
+        .iter()
+        .map(|failure| failure.error.to_string())
+        .collect();
+    failed.sort();
+    assert_eq!(failed, vec!["rejected 2", "rejected 3"]);
+    assert!(report.failures.iter().all(|failure| failure.attempts == 1));
+
+    assert_eq!(*first_seen.lock().unwrap(), vec![1, 3, 4]);
+    assert_eq!(*second_seen.lock().unwrap(), vec![1, 2, 4]);
+    assert_eq!(*healthy_seen.lock().unwrap(), vec![1, 2, 3, 4]);
+    assert_eq!(bus.dead_letters().len(), 2);
+}
+
+#[tokio::test]
+async fn sequential_dispatch_delivers_each_event_to_all_handlers_first() {
+    let mut bus = InMemoryEventBus::new();
+    let order = Arc::new(Mutex::new(Vec::new()));
+    for offset in [10, 20] {
+        bus.subscribe(Tagged {
+            offset,
+            order: order.clone(),
+        });
+    }
+
+    bus.publish(&envelopes(1..=2)).await.unwrap();
+
+    assert_eq!(*order.lock().unwrap(), vec![11, 21, 12, 22]);
+}
@@ -0,0 +1,36 @@
+use cqrs_framework::EventMetadata;
+use serde_json::json;
+use uuid::Uuid;
+
+#[test]
+fn headers_survive_a_serde_round_trip() {
+    let metadata = EventMetadata::new(Uuid::new_v4(), None)
+        .with_header("trace", "abc")
+        .with_header("region", "eu");
+
+    let json = serde_json::to_value(&metadata).unwrap();
+    assert_eq!(json["headers"], json!({ "region": "eu", "trace": "abc" }));
+
+    let decoded: EventMetadata = serde_json::from_value(json).unwrap();
+    assert_eq!(decoded.headers, metadata.headers);
+    assert_eq!(decoded.header("trace"), Some("abc"));
+}
+
+#[test]
+fn metadata_without_headers_still_deserializes() {
+    let event_id = Uuid::new_v4();
+    let correlation_id = Uuid::new_v4();
+    let legacy = json!({
+        "event_id": event_id,
+        "timestamp": "2024-01-01T00:00:00Z",
+        "correlation_id": correlation_id,
+        "causation_id": null,
+    });
+
+    let decoded: EventMetadata = serde_json::from_value(legacy).unwrap();
+
+    assert_eq!(decoded.event_id, event_id);
+    assert_eq!(decoded.correlation_id, correlation_id);
+    assert!(decoded.headers.is_empty());
+    assert!(serde_json::to_value(&decoded).unwrap().get("headers").is_none());
+}
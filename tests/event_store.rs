@@ -0,0 +1,75 @@
+mod common;
+
+use common::CounterEvent;
+use cqrs_framework::{EventEnvelope, EventMetadata, EventStore, EventStoreError, InMemoryEventStore};
+use uuid::Uuid;
+
+fn added(amounts: &[u64]) -> Vec<EventEnvelope<CounterEvent>> {
+    amounts
+        .iter()
+        .map(|&amount| {
+            EventEnvelope {
+                event: CounterEvent::Added(amount),
+                metadata: EventMetadata::new(Uuid::new_v4(), None),
+            }
+        })
+        .collect()
+}
+
+fn amounts(envelopes: Vec<EventEnvelope<CounterEvent>>) -> Vec<u64> {
+    envelopes
+        .into_iter()
+        .map(|envelope| {
+            let CounterEvent::Added(amount) = envelope.event;
+            amount
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn stale_expected_version_is_a_concurrency_conflict() {
+    let store = InMemoryEventStore::new();
+    let id = "counter-1".to_string();
+    store.save_events(&id, added(&[1, 2]), 0).await.unwrap();
+
+    let err = store.save_events(&id, added(&[3]), 1).await.unwrap_err();
+
+    assert!(err.is_concurrency_conflict());
+    assert_eq!(amounts(store.get_events(&id).await.unwrap()), vec![1, 2]);
+}
+
+#[tokio::test]
+async fn get_events_from_version_skips_earlier_events() {
+    let store = InMemoryEventStore::new();
+    let id = "counter-1".to_string();
+    store.save_events(&id, added(&[1, 2]), 0).await.unwrap();
+    store.save_events(&id, added(&[3, 4]), 2).await.unwrap();
+
+    assert_eq!(amounts(store.get_events_from_version(&id, 3).await.unwrap()), vec![4]);
+    assert!(store.get_events_from_version(&id, 4).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn read_all_positions_are_one_based_and_contiguous_across_streams() {
+    let store = InMemoryEventStore::new();
+    let first = "counter-1".to_string();
+    let second = "counter-2".to_string();
+    store.save_events(&first, added(&[1]), 0).await.unwrap();
+    store.save_events(&second, added(&[2, 3]), 0).await.unwrap();
+    store.save_events(&first, added(&[4]), 1).await.unwrap();
+
+    let stored = store.read_all(0, 10).await.unwrap();
+    let positions: Vec<_> = stored.iter().map(|event| event.position).collect();
+    let streams: Vec<_> = stored
+        .iter()
+        .map(|event| (event.aggregate_id.as_str(), event.version))
+        .collect();
+    assert_eq!(positions, vec![1, 2, 3, 4]);
+    assert_eq!(
+        streams,
+        vec![("counter-1", 1), ("counter-2", 1), ("counter-2", 2), ("counter-1", 2)]
+    );
+
+    let rest = store.read_all(2, 10).await.unwrap();
+    assert_eq!(rest.iter().map(|event| event.position).collect::<Vec<_>>(), vec![3, 4]);
+}
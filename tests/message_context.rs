@@ -0,0 +1,59 @@
+mod common;
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use common::{Add, CounterEvent, CounterHandler, TestError, add};
+use cqrs_framework::{
+    CommandHandler, EventBus, EventEnvelope, EventHandler, EventStore, InMemoryEventBus, MessageContext,
+};
+use uuid::Uuid;
+
+// Adds whatever lands on counter-1 to counter-2 as well.
+struct Mirror {
+    target: Arc<CounterHandler>,
+}
+
+#[async_trait]
+impl EventHandler<CounterEvent> for Mirror {
+    type Error = TestError;
+
+    async fn handle(&self, envelope: &EventEnvelope<CounterEvent>) -> Result<(), Self::Error> {
+        let CounterEvent::Added(amount) = envelope.event;
+        let command = Add {
+            id: "counter-2".to_string(),
+            ..add(&[amount])
+        };
+        self.target.handle(command).await?;
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn follow_up_commands_inherit_correlation_and_causation() {
+    let target = Arc::new(CounterHandler::default());
+    let mut event_bus = InMemoryEventBus::new().with_propagated_headers(["trace"]);
+    event_bus.subscribe(Mirror {
+        target: target.clone(),
+    });
+    let source = CounterHandler::default().with_event_bus(event_bus);
+
+    let correlation_id = Uuid::new_v4();
+    let context = MessageContext::new(correlation_id)
+        .with_user_id("alice")
+        .with_header("trace", "abc")
+        .with_header("session", "secret");
+    let result = context.scope(source.handle(add(&[3]))).await.unwrap();
+
+    let trigger = &result.events[0];
+    assert_eq!(trigger.header("session"), Some("secret"));
+
+    let follow_up = target.event_store.get_events(&"counter-2".to_string()).await.unwrap();
+    let metadata = &follow_up[0].metadata;
+    assert_eq!(follow_up[0].event, CounterEvent::Added(3));
+    assert_eq!(metadata.correlation_id, correlation_id);
+    assert_eq!(metadata.causation_id, Some(trigger.event_id));
+    assert_eq!(metadata.user_id.as_deref(), Some("alice"));
+    assert_eq!(metadata.header("trace"), Some("abc"));
+    assert_eq!(metadata.header("session"), None);
+}
@@ -0,0 +1,183 @@
+use std::any::Any;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+
+mod common;
+
+use async_trait::async_trait;
+use common::{Add, CounterHandler, add};
+use cqrs_framework::{
+    BusError, CommandBus, ErasedCommandResult, InMemoryCommandBus, InMemoryQueryBus, Middleware, Next, Query, QueryBus,
+    QueryHandler,
+};
+
+#[derive(Debug, Clone)]
+struct Double(u32);
+
+impl Query for Double {
+    type Result = u32;
+}
+
+#[derive(Debug)]
+struct Flaky;
+
+impl std::fmt::Display for Flaky {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result { write!(f, "flaky") }
+}
+
+impl std::error::Error for Flaky {}
+
+struct DoubleHandler {
+    failures: AtomicU32,
+    calls: Arc<AtomicU32>,
+}
+
+impl DoubleHandler {
+    fn new(failures: u32) -> (Self, Arc<AtomicU32>) {
+        let calls = Arc::new(AtomicU32::new(0));
+        let handler = Self {
+            failures: AtomicU32::new(failures),
+            calls: calls.clone(),
+        };
+        (handler, calls)
+    }
+}
+
+#[async_trait]
+impl QueryHandler<Double> for DoubleHandler {
+    type Error = Flaky;
+
+    async fn handle(&self, query: Double) -> Result<u32, Self::Error> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+
+        let failed = self
+            .failures
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |failures| failures.checked_sub(1))
+            .is_ok();
+        if failed {
+            return Err(Flaky);
+        }
+        Ok(query.0 * 2)
+    }
+}
+
+struct Recording {
+    name: &'static str,
+    log: Arc<Mutex<Vec<String>>>,
+}
+
+#[async_trait]
+impl Middleware for Recording {
+    async fn handle(
+        &self, message_type: &'static str, message: &(dyn Any + Send + Sync), next: Next<'_>,
+    ) -> Result<Box<dyn Any + Send>, BusError> {
+        self.log.lock().unwrap().push(format!("{} before", self.name));
+        let result = next.run(message_type, message).await;
+        self.log.lock().unwrap().push(format!("{} after", self.name));
+        result
+    }
+}
+
+struct Retry {
+    max_attempts: u32,
+}
+
+#[async_trait]
+impl Middleware for Retry {
+    async fn handle(
+        &self, message_type: &'static str, message: &(dyn Any + Send + Sync), next: Next<'_>,
+    ) -> Result<Box<dyn Any + Send>, BusError> {
+        let mut attempt = 1;
+        loop {
+            match next.run(message_type, message).await {
+                Err(BusError::Handler(_)) if attempt < self.max_attempts => attempt += 1,
+                result => return result,
+            }
+        }
+    }
+}
+
+// (version, event count, output) of every command that went through.
+type Audited = (u64, usize, Option<u64>);
+
+struct Audit {
+    seen: Arc<Mutex<Vec<Audited>>>,
+}
+
+#[async_trait]
+impl Middleware for Audit {
+    async fn handle(
+        &self, message_type: &'static str, message: &(dyn Any + Send + Sync), next: Next<'_>,
+    ) -> Result<Box<dyn Any + Send>, BusError> {
+        let result = next.run(message_type, message).await?;
+        if let Some(command_result) = ErasedCommandResult::from_any(result.as_ref()) {
+            self.seen.lock().unwrap().push((
+                command_result.version,
+                command_result.events.len(),
+                command_result.output_ref::<u64>().copied(),
+            ));
+        }
+        Ok(result)
+    }
+}
+
+#[tokio::test]
+async fn middleware_runs_in_registration_order_around_the_handler() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let mut bus = InMemoryQueryBus::new()
+        .with_middleware(Recording {
+            name: "outer",
+            log: log.clone(),
+        })
+        .with_middleware(Recording {
+            name: "inner",
+            log: log.clone(),
+        });
+    let (handler, _) = DoubleHandler::new(0);
+    bus.register_handler(handler);
+
+    assert_eq!(bus.send(Double(21)).await.unwrap(), 42);
+    assert_eq!(
+        *log.lock().unwrap(),
+        vec!["outer before", "inner before", "inner after", "outer after"]
+    );
+}
+
+#[tokio::test]
+async fn middleware_can_invoke_the_rest_of_the_chain_again() {
+    let mut bus = InMemoryQueryBus::new().with_middleware(Retry {
+        max_attempts: 3,
+    });
+    let (handler, calls) = DoubleHandler::new(2);
+    bus.register_handler(handler);
+
+    assert_eq!(bus.send(Double(4)).await.unwrap(), 8);
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn retry_middleware_surfaces_the_last_handler_error() {
+    let mut bus = InMemoryQueryBus::new().with_middleware(Retry {
+        max_attempts: 2,
+    });
+    let (handler, calls) = DoubleHandler::new(5);
+    bus.register_handler(handler);
+
+    let err = bus.send(Double(4)).await.unwrap_err();
+    assert!(err.downcast_ref::<Flaky>().is_some());
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn command_middleware_can_read_the_typed_result() {
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let mut bus = InMemoryCommandBus::new().with_middleware(Audit {
+        seen: seen.clone(),
+    });
+    bus.register_handler_with_output::<Add, _>(CounterHandler::default());
+
+    bus.send(add(&[1, 2])).await.unwrap();
+    bus.send_with_output(add(&[3])).await.unwrap();
+
+    assert_eq!(*seen.lock().unwrap(), vec![(2, 2, Some(3)), (3, 1, Some(6))]);
+}
@@ -0,0 +1,155 @@
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use cqrs_framework::{
+    Event, EventEnvelope, EventMetadata, EventStore, InMemoryEventStore, Projection, ProjectionRebuild,
+    ResettableProjection, ShadowProjection,
+};
+use uuid::Uuid;
+
+#[derive(Debug, Clone)]
+enum AccountEvent {
+    Opened(u32),
+    Renamed(u32),
+}
+
+impl Event for AccountEvent {
+    fn event_type(&self) -> &'static str {
+        match self {
+            AccountEvent::Opened(_) => "Opened",
+            AccountEvent::Renamed(_) => "Renamed",
+        }
+    }
+}
+
+#[derive(Default)]
+struct Recorder {
+    seen: Mutex<Vec<u32>>,
+}
+
+#[async_trait]
+impl Projection for Recorder {
+    type Error = ();
+    type Event = AccountEvent;
+
+    async fn apply(&self, event: &Self::Event) -> Result<(), Self::Error> {
+        let (AccountEvent::Opened(number) | AccountEvent::Renamed(number)) = event;
+        self.seen.lock().unwrap().push(*number);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ResettableProjection for Recorder {
+    async fn reset(&self) -> Result<(), Self::Error> {
+        self.seen.lock().unwrap().clear();
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct Live {
+    current: Recorder,
+    swapped_at: Mutex<Option<u64>>,
+}
+
+#[async_trait]
+impl Projection for Live {
+    type Error = ();
+    type Event = AccountEvent;
+
+    async fn apply(&self, event: &Self::Event) -> Result<(), Self::Error> { self.current.apply(event).await }
+}
+
+#[async_trait]
+impl ShadowProjection for Live {
+    type Shadow = Recorder;
+
+    async fn create_shadow(&self) -> Result<Self::Shadow, Self::Error> {
+        Ok(Recorder {
+            seen: Mutex::new(vec![999]),
+        })
+    }
+
+    async fn swap(&self, shadow: Self::Shadow, position: u64) -> Result<(), Self::Error> {
+        *self.current.seen.lock().unwrap() = shadow.seen.into_inner().unwrap();
+        *self.swapped_at.lock().unwrap() = Some(position);
+        Ok(())
+    }
+}
+
+async fn seeded_store() -> InMemoryEventStore<AccountEvent, String> {
+    let store = InMemoryEventStore::new();
+    let events = [
+        AccountEvent::Opened(1),
+        AccountEvent::Renamed(2),
+        AccountEvent::Opened(3),
+        AccountEvent::Renamed(4),
+        AccountEvent::Opened(5),
+    ];
+
+    for (version, event) in events.into_iter().enumerate() {
+        let envelope = EventEnvelope {
+            event,
+            metadata: EventMetadata::new(Uuid::new_v4(), None),
+        };
+        store
+            .save_events(&"account-1".to_string(), vec![envelope], version as u64)
+            .await
+            .unwrap();
+    }
+
+    store
+}
+
+#[tokio::test]
+async fn rebuild_resets_and_replays_only_the_selected_event_types() {
+    let store = seeded_store().await;
+    let projection = Recorder {
+        seen: Mutex::new(vec![42]),
+    };
+
+    let progress = ProjectionRebuild::<_, String>::new(&store)
+        .with_event_types(["Opened"])
+        .with_batch_size(2)
+        .rebuild(&projection)
+        .await
+        .unwrap();
+
+    assert_eq!(*projection.seen.lock().unwrap(), vec![1, 3, 5]);
+    assert_eq!(progress.position, 5);
+    assert_eq!(progress.events_read, 5);
+    assert_eq!(progress.events_applied, 3);
+}
+
+#[tokio::test]
+async fn rebuild_reports_progress_after_every_batch() {
+    let store = seeded_store().await;
+    let reported = Mutex::new(Vec::new());
+
+    ProjectionRebuild::<_, String>::new(&store)
+        .with_batch_size(2)
+        .on_progress(|progress| reported.lock().unwrap().push(progress.position))
+        .rebuild(&Recorder::default())
+        .await
+        .unwrap();
+
+    assert_eq!(reported.into_inner().unwrap(), vec![2, 4, 5]);
+}
+
+#[tokio::test]
+async fn shadow_rebuild_swaps_in_a_fresh_copy_at_the_last_position() {
+    let store = seeded_store().await;
+    let live = Live::default();
+    live.current.seen.lock().unwrap().push(7);
+
+    let progress = ProjectionRebuild::<_, String>::new(&store)
+        .with_event_types(["Renamed"])
+        .rebuild_shadow(&live)
+        .await
+        .unwrap();
+
+    assert_eq!(progress.position, 5);
+    assert_eq!(*live.swapped_at.lock().unwrap(), Some(5));
+    assert_eq!(*live.current.seen.lock().unwrap(), vec![2, 4]);
+}
@@ -0,0 +1,81 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use cqrs_framework::{
+    EventsSinceLastSnapshotPolicy, EveryNEventsPolicy, NeverPolicy, SnapshotContext, SnapshotPolicy, TimeBasedPolicy,
+};
+
+fn context(last_snapshot_version: u64, previous_version: u64, current_version: u64) -> SnapshotContext {
+    SnapshotContext {
+        last_snapshot_version,
+        previous_version,
+        current_version,
+        oldest_unsnapshotted_event_at: None,
+    }
+}
+
+#[test]
+fn every_n_events_snapshots_when_landing_on_a_multiple() {
+    let policy = EveryNEventsPolicy::new(10);
+
+    assert!(policy.should_snapshot(&context(0, 9, 10)));
+    assert!(!policy.should_snapshot(&context(0, 10, 11)));
+    assert!(!policy.should_snapshot(&context(0, 0, 9)));
+}
+
+#[test]
+fn every_n_events_snapshots_when_a_batch_jumps_past_a_multiple() {
+    let policy = EveryNEventsPolicy::new(10);
+
+    assert!(policy.should_snapshot(&context(0, 8, 11)));
+    assert!(policy.should_snapshot(&context(10, 19, 35)));
+    assert!(!policy.should_snapshot(&context(10, 11, 19)));
+}
+
+#[test]
+fn every_n_events_with_zero_interval_never_snapshots() {
+    let policy = EveryNEventsPolicy::new(0);
+
+    assert!(!policy.should_snapshot(&context(0, 0, 10)));
+    assert!(!policy.should_snapshot(&context(0, 9, 100)));
+}
+
+#[test]
+fn events_since_last_snapshot_counts_from_the_last_snapshot() {
+    let policy = EventsSinceLastSnapshotPolicy::new(5);
+
+    assert!(!policy.should_snapshot(&context(10, 12, 14)));
+    assert!(policy.should_snapshot(&context(10, 12, 15)));
+    assert!(policy.should_snapshot(&context(0, 0, 7)));
+}
+
+#[test]
+fn events_since_last_snapshot_with_zero_threshold_snapshots_on_any_new_event() {
+    let policy = EventsSinceLastSnapshotPolicy::new(0);
+
+    assert!(policy.should_snapshot(&context(3, 3, 4)));
+    assert!(!policy.should_snapshot(&context(3, 3, 3)));
+}
+
+#[test]
+fn time_based_snapshots_once_the_oldest_event_is_old_enough() {
+    let policy = TimeBasedPolicy::new(Duration::from_secs(60));
+
+    let recent = SnapshotContext {
+        oldest_unsnapshotted_event_at: Some(Utc::now() - chrono::Duration::seconds(10)),
+        ..context(0, 1, 2)
+    };
+    let stale = SnapshotContext {
+        oldest_unsnapshotted_event_at: Some(Utc::now() - chrono::Duration::seconds(120)),
+        ..context(0, 1, 2)
+    };
+
+    assert!(!policy.should_snapshot(&recent));
+    assert!(policy.should_snapshot(&stale));
+    assert!(!policy.should_snapshot(&context(0, 1, 2)));
+}
+
+#[test]
+fn never_policy_never_snapshots() {
+    assert!(!NeverPolicy.should_snapshot(&context(0, 9, 1000)));
+}
@@ -0,0 +1,242 @@
+mod common;
+
+use std::convert::Infallible;
+
+use async_trait::async_trait;
+use common::{Counter, CounterEvent, CounterHandler, InMemorySnapshots, TestError, add};
+use cqrs_framework::{
+    Aggregate, Command, CommandHandler, EventEnvelope, EventStore, InMemoryEventBus, InMemoryEventStore,
+    InMemoryEventStoreError, SnapshotStore, StoredEvent,
+};
+
+// Reads back every stored `Added(n)` with an even `n` as two `Added(n / 2)`
+// events at the same version, the way an upcaster splitting one event would.
+#[derive(Default)]
+struct SplittingEventStore {
+    inner: InMemoryEventStore<CounterEvent, String>,
+}
+
+#[async_trait]
+impl EventStore<CounterEvent, String> for SplittingEventStore {
+    type Error = InMemoryEventStoreError;
+
+    async fn save_events(
+        &self, aggregate_id: &String, events: Vec<EventEnvelope<CounterEvent>>, expected_version: u64,
+    ) -> Result<(), Self::Error> {
+        self.inner.save_events(aggregate_id, events, expected_version).await
+    }
+
+    async fn get_events(&self, aggregate_id: &String) -> Result<Vec<EventEnvelope<CounterEvent>>, Self::Error> {
+        self.get_events_from_version(aggregate_id, 0).await
+    }
+
+    async fn get_events_from_version(
+        &self, aggregate_id: &String, from_version: u64,
+    ) -> Result<Vec<EventEnvelope<CounterEvent>>, Self::Error> {
+        Ok(self
+            .get_stream_from_version(aggregate_id, from_version)
+            .await?
+            .into_iter()
+            .map(|(_, envelope)| envelope)
+            .collect())
+    }
+
+    async fn get_stream_from_version(
+        &self, aggregate_id: &String, from_version: u64,
+    ) -> Result<Vec<(u64, EventEnvelope<CounterEvent>)>, Self::Error> {
+        let stream = self.inner.get_stream_from_version(aggregate_id, from_version).await?;
+
+        Ok(stream
+            .into_iter()
+            .flat_map(|(version, envelope)| {
+                let CounterEvent::Added(amount) = envelope.event;
+                let split = if amount % 2 == 0 {
+                    vec![amount / 2, amount / 2]
+                } else {
+                    vec![amount]
+                };
+                split.into_iter().map(move |amount| {
+                    let envelope = EventEnvelope {
+                        event: CounterEvent::Added(amount),
+                        metadata: envelope.metadata.clone(),
+                    };
+                    (version, envelope)
+                })
+            })
+            .collect())
+    }
+
+    async fn read_all(
+        &self, from_position: u64, batch_size: usize,
+    ) -> Result<Vec<StoredEvent<CounterEvent, String>>, Self::Error> {
+        self.inner.read_all(from_position, batch_size).await
+    }
+}
+
+// Bumps its own version inside `apply`, as aggregates written before
+// `replay` existed do.
+#[derive(Debug, Clone, Default)]
+struct LegacyCounter {
+    total: u64,
+    version: u64,
+}
+
+impl Aggregate for LegacyCounter {
+    type Event = CounterEvent;
+
+    fn apply(&mut self, event: Self::Event) {
+        match event {
+            CounterEvent::Added(amount) => self.total += amount,
+        }
+        self.version += 1;
+    }
+
+    fn version(&self) -> u64 { self.version }
+
+    fn increment_version(&mut self) { self.version += 1; }
+
+    fn aggregate_type() -> &'static str { "LegacyCounter" }
+}
+
+struct LegacyAdd {
+    id: String,
+    amounts: Vec<u64>,
+}
+
+fn legacy_add(amounts: &[u64]) -> LegacyAdd {
+    LegacyAdd {
+        id: "counter-1".to_string(),
+        amounts: amounts.to_vec(),
+    }
+}
+
+impl Command for LegacyAdd {
+    type Aggregate = LegacyCounter;
+    type AggregateId = String;
+    type Error = Infallible;
+
+    fn aggregate_id(&self) -> &Self::AggregateId { &self.id }
+
+    fn execute(&self, _aggregate: &Self::Aggregate) -> Result<Vec<CounterEvent>, Self::Error> {
+        Ok(self.amounts.iter().copied().map(CounterEvent::Added).collect())
+    }
+}
+
+#[derive(Default)]
+struct LegacyHandler {
+    event_store: InMemoryEventStore<CounterEvent, String>,
+    snapshot_store: InMemorySnapshots<LegacyCounter>,
+    event_bus: InMemoryEventBus<CounterEvent>,
+}
+
+impl CommandHandler<LegacyAdd> for LegacyHandler {
+    type Error = TestError;
+    type EventBus = InMemoryEventBus<CounterEvent>;
+    type EventStore = InMemoryEventStore<CounterEvent, String>;
+    type SnapshotStore = InMemorySnapshots<LegacyCounter>;
+
+    fn event_store(&self) -> &Self::EventStore { &self.event_store }
+
+    fn snapshot_store(&self) -> &Self::SnapshotStore { &self.snapshot_store }
+
+    fn event_bus(&self) -> &Self::EventBus { &self.event_bus }
+}
+
+async fn rebuild_from_events<S: EventStore<CounterEvent, String>>(handler: &CounterHandler<S>) -> Counter
+where
+    S::Error: std::fmt::Debug,
+{
+    let mut counter = Counter::default();
+    for envelope in handler.event_store.get_events(&"counter-1".to_string()).await.unwrap() {
+        counter.replay(envelope.event);
+    }
+    counter
+}
+
+#[tokio::test]
+async fn snapshot_reflects_state_at_its_version() {
+    let handler: CounterHandler = CounterHandler::default();
+
+    handler.handle(add(&[1, 2, 3, 4])).await.unwrap();
+    handler.handle(add(&[5, 6, 7, 8])).await.unwrap();
+    assert!(handler.snapshot_store.snapshots.lock().unwrap().is_empty());
+
+    handler.handle(add(&[9, 10, 11])).await.unwrap();
+
+    let snapshot = handler
+        .snapshot_store
+        .get_snapshot(&"counter-1".to_string())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(snapshot.version(), 11);
+    assert_eq!(snapshot.total, (1..=11).sum::<u64>());
+
+    let replayed = rebuild_from_events(&handler).await;
+    assert_eq!(snapshot.version(), replayed.version());
+    assert_eq!(snapshot.total, replayed.total);
+}
+
+#[tokio::test]
+async fn reloading_from_snapshot_neither_skips_nor_reapplies_events() {
+    let handler: CounterHandler = CounterHandler::default();
+
+    handler.handle(add(&[1; 10])).await.unwrap();
+    handler.handle(add(&[2; 3])).await.unwrap();
+    handler.handle(add(&[3; 8])).await.unwrap();
+
+    let snapshot = handler
+        .snapshot_store
+        .get_snapshot(&"counter-1".to_string())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(snapshot.version(), 21);
+    assert_eq!(snapshot.total, 10 + 6 + 24);
+
+    let replayed = rebuild_from_events(&handler).await;
+    assert_eq!(replayed.version(), 21);
+    assert_eq!(replayed.total, snapshot.total);
+}
+
+#[tokio::test]
+async fn events_split_on_read_keep_their_stored_version() {
+    let handler = CounterHandler::new(SplittingEventStore::default());
+
+    handler.handle(add(&[4, 1, 6])).await.unwrap();
+    let result = handler.handle(add(&[2])).await.unwrap();
+    assert_eq!(result.version, 4);
+
+    let replayed = rebuild_from_events(&handler).await;
+    assert_eq!(replayed.total, 13);
+}
+
+#[tokio::test]
+async fn aggregates_bumping_their_own_version_are_not_double_counted() {
+    let handler = LegacyHandler::default();
+
+    let result = handler.handle(legacy_add(&[1; 6])).await.unwrap();
+    assert_eq!(result.version, 6);
+
+    let result = handler.handle(legacy_add(&[2; 6])).await.unwrap();
+    assert_eq!(result.version, 12);
+
+    let snapshot = handler
+        .snapshot_store
+        .get_snapshot(&"counter-1".to_string())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(snapshot.version(), 12);
+    assert_eq!(snapshot.total, 18);
+
+    let result = handler.handle(legacy_add(&[3])).await.unwrap();
+    assert_eq!(result.version, 13);
+
+    let mut replayed = LegacyCounter::default();
+    for envelope in handler.event_store.get_events(&"counter-1".to_string()).await.unwrap() {
+        replayed.replay(envelope.event);
+    }
+    assert_eq!(replayed.version(), 13);
+    assert_eq!(replayed.total, 21);
+}
@@ -0,0 +1,156 @@
+use cqrs_framework::{RawEvent, UpcastError, Upcaster, UpcasterRegistry};
+use serde_json::json;
+
+struct RenameAmount;
+
+impl Upcaster for RenameAmount {
+    fn event_type(&self) -> &str { "Added" }
+
+    fn schema_version(&self) -> u32 { 1 }
+
+    fn upcast(&self, event: RawEvent) -> Vec<RawEvent> {
+        vec![RawEvent {
+            schema_version: 2,
+            data: json!({ "Added": { "amount": event.data["Added"]["value"] } }),
+            ..event
+        }]
+    }
+}
+
+struct SplitTransfer;
+
+impl Upcaster for SplitTransfer {
+    fn event_type(&self) -> &str { "Transferred" }
+
+    fn schema_version(&self) -> u32 { 1 }
+
+    fn upcast(&self, event: RawEvent) -> Vec<RawEvent> {
+        let amount = &event.data["Transferred"]["amount"];
+
+        vec![
+            RawEvent {
+                event_type: "Removed".to_string(),
+                schema_version: 1,
+                data: json!({ "Removed": { "amount": amount } }),
+            },
+            RawEvent {
+                event_type: "Added".to_string(),
+                schema_version: 1,
+                data: json!({ "Added": { "value": amount } }),
+            },
+        ]
+    }
+}
+
+struct Rename {
+    from: &'static str,
+    to: &'static str,
+    schema_version: u32,
+}
+
+impl Upcaster for Rename {
+    fn event_type(&self) -> &str { self.from }
+
+    fn schema_version(&self) -> u32 { self.schema_version }
+
+    fn upcast(&self, event: RawEvent) -> Vec<RawEvent> {
+        vec![RawEvent {
+            event_type: self.to.to_string(),
+            ..event
+        }]
+    }
+}
+
+fn registry() -> UpcasterRegistry {
+    UpcasterRegistry::new()
+        .with_upcaster(RenameAmount)
+        .with_upcaster(SplitTransfer)
+}
+
+#[test]
+fn current_events_pass_through_unchanged() {
+    let event = RawEvent {
+        event_type: "Added".to_string(),
+        schema_version: 2,
+        data: json!({ "Added": { "amount": 5 } }),
+    };
+
+    assert_eq!(registry().upcast(event.clone()).unwrap(), vec![event]);
+}
+
+#[test]
+fn split_events_are_upcast_in_order_through_every_version() {
+    let upcasted = registry()
+        .upcast(RawEvent {
+            event_type: "Transferred".to_string(),
+            schema_version: 1,
+            data: json!({ "Transferred": { "amount": 7 } }),
+        })
+        .unwrap();
+
+    assert_eq!(
+        upcasted,
+        vec![
+            RawEvent {
+                event_type: "Removed".to_string(),
+                schema_version: 1,
+                data: json!({ "Removed": { "amount": 7 } }),
+            },
+            RawEvent {
+                event_type: "Added".to_string(),
+                schema_version: 2,
+                data: json!({ "Added": { "amount": 7 } }),
+            },
+        ]
+    );
+}
+
+#[test]
+fn upcasters_that_do_not_raise_the_version_are_rejected() {
+    let registry = UpcasterRegistry::new().with_upcaster(Rename {
+        from: "Added",
+        to: "Added",
+        schema_version: 1,
+    });
+
+    let err = registry
+        .upcast(RawEvent {
+            event_type: "Added".to_string(),
+            schema_version: 1,
+            data: json!({}),
+        })
+        .unwrap_err();
+
+    assert_eq!(
+        err,
+        UpcastError::NotUpgraded {
+            event_type: "Added".to_string(),
+            schema_version: 1,
+        }
+    );
+}
+
+#[test]
+fn upcast_cycles_fail_instead_of_recursing_forever() {
+    let registry = UpcasterRegistry::new()
+        .with_upcaster(Rename {
+            from: "Added",
+            to: "Credited",
+            schema_version: 1,
+        })
+        .with_upcaster(Rename {
+            from: "Credited",
+            to: "Added",
+            schema_version: 1,
+        });
+
+    let err = registry
+        .upcast(RawEvent {
+            event_type: "Added".to_string(),
+            schema_version: 1,
+            data: json!({}),
+        })
+        .unwrap_err();
+
+    assert!(matches!(err, UpcastError::TooDeep { .. }));
+}